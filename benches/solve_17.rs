@@ -1,5 +1,5 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use sudoku::prepare;
+use sudoku::{is_unique_fast, prepare, Possibilities, SearchTree};
 
 pub fn criterion_benchmark(c: &mut Criterion) {
     let puzzle = [
@@ -16,6 +16,22 @@ pub fn criterion_benchmark(c: &mut Criterion) {
     c.bench_function("solve 17", |b| {
         b.iter(|| prepare(black_box(&puzzle)).unwrap())
     });
+
+    let clues = prepare(&puzzle).unwrap().clues();
+    c.bench_function("solve 17 batch", |b| {
+        b.iter(|| Possibilities::new().set_batch(black_box(&clues)).unwrap())
+    });
+
+    let possibilities = prepare(&puzzle).unwrap();
+    c.bench_function("unique 17 count", |b| {
+        b.iter(|| {
+            let search = SearchTree::new(black_box(&possibilities));
+            search.max_solutions(2).run().len() == 1
+        })
+    });
+    c.bench_function("unique 17 fast", |b| {
+        b.iter(|| is_unique_fast(black_box(&possibilities)))
+    });
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -0,0 +1,99 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use sudoku::{prepare, Template};
+
+pub fn criterion_benchmark(c: &mut Criterion) {
+    let puzzle = [
+        [0, 0, 0, 0, 0, 0, 0, 1, 0],
+        [0, 0, 0, 0, 0, 2, 0, 0, 3],
+        [0, 0, 0, 4, 0, 0, 0, 0, 0],
+        [0, 0, 0, 0, 0, 0, 5, 0, 0],
+        [4, 0, 1, 6, 0, 0, 0, 0, 0],
+        [0, 0, 7, 1, 0, 0, 0, 0, 0],
+        [0, 5, 0, 0, 0, 0, 2, 0, 0],
+        [0, 0, 0, 0, 8, 0, 0, 4, 0],
+        [0, 3, 0, 9, 1, 0, 0, 0, 0],
+    ];
+    let patterns = *prepare(&puzzle).unwrap().patterns();
+
+    c.bench_function("build template cache", |b| b.iter(Template::compute_all));
+
+    // warm the caches
+    Template::within_lazy(patterns[0]).count();
+
+    c.bench_function("within", |b| {
+        b.iter(|| {
+            for &p in black_box(&patterns) {
+                black_box(Template::within(p).count());
+            }
+        })
+    });
+    c.bench_function("within_lazy", |b| {
+        b.iter(|| {
+            for &p in black_box(&patterns) {
+                black_box(Template::within_lazy(p).count());
+            }
+        })
+    });
+
+    // sparse patterns from a harder puzzle
+    let hard = sudoku::parse(
+        "4.....8.5.3..........7......2.....6.....8.4......1.......6.3.7.5..2.....1.4......",
+    )
+    .unwrap();
+    let hard = *prepare(&hard).unwrap().patterns();
+    Template::within_sparse(hard[0]);
+
+    c.bench_function("within hard", |b| {
+        b.iter(|| {
+            for &p in black_box(&hard) {
+                black_box(Template::within(p).count());
+            }
+        })
+    });
+    c.bench_function("within_fast hard", |b| {
+        b.iter(|| {
+            for &p in black_box(&hard) {
+                black_box(Template::within_fast(p).count());
+            }
+        })
+    });
+    c.bench_function("within_sparse hard", |b| {
+        b.iter(|| {
+            for &p in black_box(&hard) {
+                black_box(Template::within_sparse(p).len());
+            }
+        })
+    });
+    c.bench_function("within_sparse full", |b| {
+        b.iter(|| black_box(Template::within_sparse(black_box(sudoku::Pattern::FULL)).len()))
+    });
+
+    // overlap checks against a half-filled grid
+    let filled = patterns[0] & patterns[1];
+    let filled_masks: [u16; 9] = std::array::from_fn(|row| {
+        (0..9)
+            .filter(|&col| filled.has(row, col))
+            .fold(0, |m, col| m | 1 << col)
+    });
+    let row_masks = Template::all_precomputed_row_masks();
+
+    c.bench_function("intersects pattern", |b| {
+        b.iter(|| {
+            Template::all()
+                .iter()
+                .filter(|p| p.intersects(black_box(filled)))
+                .count()
+        })
+    });
+    c.bench_function("intersects row masks", |b| {
+        b.iter(|| {
+            row_masks
+                .iter()
+                .filter(|masks| (0..9).any(|row| masks[row] & black_box(filled_masks)[row] != 0))
+                .count()
+        })
+    });
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,31 @@
+//! Feed arbitrary bytes to the parser and the flat-input solver.
+//!
+//! Run with `cargo fuzz run parse_solve` from the crate root.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sudoku::{parse, solve, Possibilities, SearchTree, Solution};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(text) = std::str::from_utf8(data) {
+        if let Ok(grid) = parse(text) {
+            if let Ok(puzzle) = Possibilities::try_from(grid) {
+                for solution in SearchTree::new(&puzzle).max_solutions(2).run() {
+                    assert!(solution.is_valid());
+                }
+            }
+        }
+    }
+
+    for solution in solve(data.to_vec(), 2) {
+        // every solution is a full grid that agrees with the clues
+        assert_eq!(solution.len(), 81);
+        for (clue, cell) in data.iter().zip(solution.bytes()) {
+            assert!((b'1'..=b'9').contains(&cell));
+            assert!(*clue == 0 || *clue == cell - b'0');
+        }
+        let solution = Solution::try_from(solution.as_str()).expect("not a solution grid");
+        assert!(solution.is_valid());
+    }
+});
@@ -0,0 +1,45 @@
+use std::io::Read;
+use std::process::ExitCode;
+
+use sudoku::{parse, solve_classify, GridFormatter, PuzzleFormatter, SolveResult};
+
+/// Solve a puzzle given as an argument, or on stdin.
+///
+/// Exit codes:  0 for a unique solution, 1 for none, 2 for several, 3 for
+/// unreadable input.
+fn main() -> ExitCode {
+    let input = match std::env::args().nth(1) {
+        Some(arg) => arg,
+        None => {
+            let mut buf = String::new();
+            if let Err(e) = std::io::stdin().read_to_string(&mut buf) {
+                eprintln!("couldn't read stdin: {}", e);
+                return ExitCode::from(3);
+            }
+            buf
+        }
+    };
+
+    let puzzle = match parse(&input) {
+        Ok(puzzle) => puzzle,
+        Err(e) => {
+            eprintln!("couldn't read puzzle: {}", e);
+            return ExitCode::from(3);
+        }
+    };
+
+    match solve_classify(&puzzle, 2) {
+        SolveResult::Impossible => {
+            println!("no solution");
+            ExitCode::from(1)
+        }
+        SolveResult::Unique(solution) => {
+            print!("{}", GridFormatter.format_solution(&solution));
+            ExitCode::SUCCESS
+        }
+        SolveResult::Multiple(_) => {
+            println!("multiple solutions");
+            ExitCode::from(2)
+        }
+    }
+}
@@ -0,0 +1,271 @@
+//! Knuth's Algorithm X with Dancing Links, as a cross-check for the template
+//! search.
+//!
+//! Sudoku as exact cover:  each of the 729 (row, column, digit) choices covers
+//! four of the 324 constraints (cell, row-digit, column-digit, box-digit).
+
+use crate::{Pattern, Solution, Template};
+
+/// Toroidal doubly-linked lists.  Node 0 is the root, nodes `1..=324` are
+/// column headers, and the rest are choices.
+struct Links {
+    left: Vec<usize>,
+    right: Vec<usize>,
+    up: Vec<usize>,
+    down: Vec<usize>,
+    /// [node] -> column header
+    column: Vec<usize>,
+    /// [node] -> choice, `81 * digit + 9 * row + col`
+    choice: Vec<usize>,
+    /// [header] -> number of nodes in column
+    size: Vec<usize>,
+}
+
+const COLUMNS: usize = 324;
+
+impl Links {
+    fn new() -> Links {
+        let mut links = Links {
+            left: (0..=COLUMNS)
+                .map(|i| (i + COLUMNS) % (COLUMNS + 1))
+                .collect(),
+            right: (0..=COLUMNS).map(|i| (i + 1) % (COLUMNS + 1)).collect(),
+            up: (0..=COLUMNS).collect(),
+            down: (0..=COLUMNS).collect(),
+            column: (0..=COLUMNS).collect(),
+            choice: vec![usize::MAX; COLUMNS + 1],
+            size: vec![0; COLUMNS + 1],
+        };
+
+        for digit in 0..9 {
+            for row in 0..9 {
+                for col in 0..9 {
+                    let box_ = row / 3 * 3 + col / 3;
+                    links.add_choice(
+                        81 * digit + 9 * row + col,
+                        [
+                            9 * row + col,
+                            81 + 9 * row + digit,
+                            162 + 9 * col + digit,
+                            243 + 9 * box_ + digit,
+                        ],
+                    );
+                }
+            }
+        }
+        links
+    }
+
+    fn add_choice(&mut self, choice: usize, columns: [usize; 4]) {
+        let first = self.left.len();
+        for (i, column) in columns.into_iter().enumerate() {
+            let header = column + 1;
+            let node = first + i;
+
+            self.left.push(if i == 0 { first + 3 } else { node - 1 });
+            self.right.push(if i == 3 { first } else { node + 1 });
+            self.up.push(self.up[header]);
+            self.down.push(header);
+            self.column.push(header);
+            self.choice.push(choice);
+
+            let above = self.up[header];
+            self.down[above] = node;
+            self.up[header] = node;
+            self.size[header] += 1;
+        }
+    }
+
+    fn cover(&mut self, header: usize) {
+        self.right[self.left[header]] = self.right[header];
+        self.left[self.right[header]] = self.left[header];
+
+        let mut i = self.down[header];
+        while i != header {
+            let mut j = self.right[i];
+            while j != i {
+                self.down[self.up[j]] = self.down[j];
+                self.up[self.down[j]] = self.up[j];
+                self.size[self.column[j]] -= 1;
+                j = self.right[j];
+            }
+            i = self.down[i];
+        }
+    }
+
+    fn uncover(&mut self, header: usize) {
+        let mut i = self.up[header];
+        while i != header {
+            let mut j = self.left[i];
+            while j != i {
+                self.size[self.column[j]] += 1;
+                self.down[self.up[j]] = j;
+                self.up[self.down[j]] = j;
+                j = self.left[j];
+            }
+            i = self.up[i];
+        }
+
+        self.right[self.left[header]] = header;
+        self.left[self.right[header]] = header;
+    }
+
+    /// Cover the other columns of the node's choice.
+    fn select(&mut self, node: usize) {
+        let mut j = self.right[node];
+        while j != node {
+            self.cover(self.column[j]);
+            j = self.right[j];
+        }
+    }
+
+    /// Undo [`Links::select`].
+    fn deselect(&mut self, node: usize) {
+        let mut j = self.left[node];
+        while j != node {
+            self.uncover(self.column[j]);
+            j = self.left[j];
+        }
+    }
+
+    fn search(&mut self, chosen: &mut Vec<usize>, out: &mut Vec<Solution>, max_solutions: usize) {
+        if self.right[0] == 0 {
+            out.push(to_solution(chosen));
+            return;
+        }
+
+        // column with the fewest choices
+        let mut header = self.right[0];
+        let mut j = self.right[header];
+        while j != 0 {
+            if self.size[j] < self.size[header] {
+                header = j;
+            }
+            j = self.right[j];
+        }
+
+        self.cover(header);
+        let mut node = self.down[header];
+        while node != header && out.len() < max_solutions {
+            chosen.push(self.choice[node]);
+            self.select(node);
+            self.search(chosen, out, max_solutions);
+            self.deselect(node);
+            chosen.pop();
+            node = self.down[node];
+        }
+        self.uncover(header);
+    }
+}
+
+fn to_solution(chosen: &[usize]) -> Solution {
+    let mut patterns = [Pattern::EMPTY; 9];
+    for &choice in chosen {
+        let (digit, cell) = (choice / 81, choice % 81);
+        patterns[digit] = patterns[digit].with(cell / 9, cell % 9);
+    }
+    Solution(patterns.map(|p| Template::from_pattern(p).expect("not a template")))
+}
+
+/// Solve a puzzle with Dancing Links, stopping after a maximum number of
+/// solutions.
+///
+/// This finds the same solutions as the template search, possibly in a
+/// different order.
+pub fn solve_dlx(puzzle: &[[u8; 9]; 9], max_solutions: usize) -> Vec<Solution> {
+    let mut links = Links::new();
+    let mut chosen = Vec::new();
+
+    // Clues are chosen up front.  A clue whose constraint is already covered
+    // conflicts with an earlier clue.
+    let mut covered = [false; COLUMNS + 1];
+    for (row, digits) in puzzle.iter().enumerate() {
+        for (col, &digit) in digits.iter().enumerate() {
+            let digit = match digit {
+                0 => continue,
+                digit @ 1..=9 => digit as usize - 1,
+                _ => return Vec::new(),
+            };
+            let choice = 81 * digit + 9 * row + col;
+            let node = (COLUMNS + 1..links.choice.len())
+                .find(|&node| links.choice[node] == choice)
+                .unwrap();
+
+            let mut j = node;
+            loop {
+                let header = links.column[j];
+                if covered[header] {
+                    return Vec::new();
+                }
+                covered[header] = true;
+                links.cover(header);
+                j = links.right[j];
+                if j == node {
+                    break;
+                }
+            }
+            chosen.push(choice);
+        }
+    }
+
+    let mut out = Vec::new();
+    if max_solutions > 0 {
+        links.search(&mut chosen, &mut out, max_solutions);
+    }
+    out
+}
+
+#[cfg(all(test, feature = "dlx"))]
+mod tests {
+    use super::*;
+    use crate::{parse, solve};
+
+    /// Solutions from both solvers, sorted.
+    fn both(input: &str, max_solutions: usize) -> (Vec<String>, Vec<String>) {
+        let puzzle = parse(input).unwrap();
+        let mut dlx: Vec<String> = solve_dlx(&puzzle, max_solutions)
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let mut native = solve(puzzle.concat(), max_solutions);
+        dlx.sort();
+        native.sort();
+        (dlx, native)
+    }
+
+    #[test]
+    fn unique_puzzles_match() {
+        for input in [
+            "53..7....6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79",
+            "4.....8.5.3..........7......2.....6.....8.4......1.......6.3.7.5..2.....1.4......",
+            "8..........36......7..9.2...5...7.......457.....1...3...1....68..85...1..9....4..",
+            "8.2.17..47.3..6....94..37...3..9.1.7.8..3125.1.9..26.3...1.43.....27......8....12",
+        ] {
+            let (dlx, native) = both(input, 2);
+            assert_eq!(dlx.len(), 1, "{}", input);
+            assert_eq!(dlx, native, "{}", input);
+        }
+    }
+
+    #[test]
+    fn invalid_puzzle_has_no_solutions() {
+        // two 5s in the first row
+        let input =
+            "53..7...56..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79";
+        assert_eq!(both(input, 2), (vec![], vec![]));
+        assert!(solve_dlx(&[[10; 9]; 9], 2).is_empty());
+    }
+
+    #[test]
+    fn multiple_solutions_match() {
+        let input =
+            ".........6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79";
+        let (dlx, native) = both(input, 100);
+        assert!(dlx.len() > 1);
+        assert_eq!(dlx, native);
+
+        let (dlx, native) = both(input, 1);
+        assert_eq!((dlx.len(), native.len()), (1, 1));
+        assert!(solve_dlx(&parse(input).unwrap(), 0).is_empty());
+    }
+}
@@ -0,0 +1,197 @@
+use crate::{Possibilities, Solution};
+
+/// Text representation of puzzles and solutions.
+///
+/// See [`CompactFormatter`], [`GridFormatter`], and [`PencilmarkFormatter`].
+pub trait PuzzleFormatter {
+    /// Write the clues of a puzzle.
+    fn format_clues(&self, poss: &Possibilities) -> String;
+    /// Write a full solution.
+    fn format_solution(&self, sol: &Solution) -> String;
+}
+
+/// 81 characters in row-major order, `.` for an empty cell.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CompactFormatter;
+
+/// Nine lines with `|` and `---+---+---` between boxes, like [`Pattern`](crate::Pattern).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GridFormatter;
+
+/// Every digit still possible in every cell, after logic.
+///
+/// See the [`Display`](std::fmt::Display) impl for [`Possibilities`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PencilmarkFormatter;
+
+/// Formatter with the given name:  `"compact"`, `"grid"`, or `"pencilmark"`.
+pub fn formatter_by_name(name: &str) -> Option<&'static dyn PuzzleFormatter> {
+    match name {
+        "compact" => Some(&CompactFormatter),
+        "grid" => Some(&GridFormatter),
+        "pencilmark" => Some(&PencilmarkFormatter),
+        _ => None,
+    }
+}
+
+/// Digits in row-major order, 0 for an empty cell.
+pub(crate) fn clue_cells(poss: &Possibilities) -> [u8; 81] {
+    let mut cells = [0; 81];
+    for (row, col, digit) in poss.clues() {
+        cells[9 * row + col] = digit;
+    }
+    cells
+}
+
+fn cell_char(digit: u8) -> char {
+    match digit {
+        0 => '.',
+        _ => (b'0' + digit) as char,
+    }
+}
+
+/// Sudoku Exchange form of a grid:  81 digits in row-major order, `0` for an
+/// empty cell.
+pub(crate) fn exchange_format(cells: &[u8]) -> String {
+    cells.iter().map(|&digit| (b'0' + digit) as char).collect()
+}
+
+fn compact(cells: &[u8]) -> String {
+    cells.iter().map(|&digit| cell_char(digit)).collect()
+}
+
+fn grid(cells: &[u8]) -> String {
+    let mut out = String::new();
+    for row in 0..9 {
+        if row == 3 || row == 6 {
+            out.push_str("---+---+---\n");
+        }
+        for col in 0..9 {
+            if col == 3 || col == 6 {
+                out.push('|');
+            }
+            out.push(cell_char(cells[9 * row + col]));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+impl PuzzleFormatter for CompactFormatter {
+    fn format_clues(&self, poss: &Possibilities) -> String {
+        compact(&clue_cells(poss))
+    }
+    fn format_solution(&self, sol: &Solution) -> String {
+        compact(&sol.to_grid())
+    }
+}
+
+impl PuzzleFormatter for GridFormatter {
+    fn format_clues(&self, poss: &Possibilities) -> String {
+        grid(&clue_cells(poss))
+    }
+    fn format_solution(&self, sol: &Solution) -> String {
+        grid(&sol.to_grid())
+    }
+}
+
+impl PuzzleFormatter for PencilmarkFormatter {
+    fn format_clues(&self, poss: &Possibilities) -> String {
+        format!("{}", poss)
+    }
+    /// A solution has only one possible digit per cell.
+    fn format_solution(&self, sol: &Solution) -> String {
+        grid(&sol.to_grid())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUZZLE: &str =
+        "4.....8.5.3..........7......2.....6.....8.4......1.......6.3.7.5..2.....1.4......";
+    const SOLUTION: &str =
+        "417369825632158947958724316825437169791586432346912758289643571573291684164875293";
+
+    fn formatted(name: &str) -> (String, String) {
+        let formatter = formatter_by_name(name).unwrap();
+        let puzzle = Possibilities::try_from(PUZZLE).unwrap();
+        let solution = Solution::try_from(SOLUTION).unwrap();
+        (
+            formatter.format_clues(&puzzle),
+            formatter.format_solution(&solution),
+        )
+    }
+
+    const SOLUTION_GRID: &str = concat!(
+        "417|369|825\n",
+        "632|158|947\n",
+        "958|724|316\n",
+        "---+---+---\n",
+        "825|437|169\n",
+        "791|586|432\n",
+        "346|912|758\n",
+        "---+---+---\n",
+        "289|643|571\n",
+        "573|291|684\n",
+        "164|875|293\n",
+    );
+
+    #[test]
+    fn compact_output() {
+        assert_eq!(
+            formatted("compact"),
+            (PUZZLE.to_string(), SOLUTION.to_string())
+        );
+    }
+
+    #[test]
+    fn grid_output() {
+        let clues = concat!(
+            "4..|...|8.5\n",
+            ".3.|...|...\n",
+            "...|7..|...\n",
+            "---+---+---\n",
+            ".2.|...|.6.\n",
+            "...|.8.|4..\n",
+            "...|.1.|...\n",
+            "---+---+---\n",
+            "...|6.3|.7.\n",
+            "5..|2..|...\n",
+            "1.4|...|...\n",
+        );
+        assert_eq!(
+            formatted("grid"),
+            (clues.to_string(), SOLUTION_GRID.to_string())
+        );
+    }
+
+    #[test]
+    fn pencilmark_output() {
+        let clues = concat!(
+            "   4     |1    67 9|12   67 9||1 3     9| 23  6  9| 2   6  9||       8 |123     9|    5    \n",
+            " 2   6789|  3      |12  56789||1  45  89| 2 456  9| 2 456 89||12   67 9|12 4    9|12 4 67 9\n",
+            " 2   6 89|1   56 89|12  56 89||      7  | 23456  9| 2 456 89||123  6  9|1234    9|1234 6  9\n",
+            "===========================================================================================\n",
+            "  3   789| 2       |1   5 789||  345   9|  345 7 9|   45 7 9||1 3 5 7 9|     6   |1 3   789\n",
+            "  3  67 9|1   567 9|1   567 9||  3 5   9|       8 | 2  567 9||   4     |123 5   9|123   7 9\n",
+            "  3  6789|   4     |    56789||  3 5   9|1        | 2  567 9|| 23 5 7 9| 23 5  89| 23   789\n",
+            "===========================================================================================\n",
+            " 2     89|       89| 2     89||     6   |   45   9|  3      ||12  5   9|      7  |12 4   89\n",
+            "    5    |     6789|  3      || 2       |   4  7 9|1        ||     6  9|   4   89|   4 6 89\n",
+            "1        |     6789|   4     ||    5  89|    5 7 9|    5 789|| 23 56  9| 23 5  89| 23  6 89\n",
+        );
+        assert_eq!(
+            formatted("pencilmark"),
+            (clues.to_string(), SOLUTION_GRID.to_string())
+        );
+    }
+
+    #[test]
+    fn unknown_name_gives_none() {
+        assert!(formatter_by_name("").is_none());
+        assert!(formatter_by_name("Grid").is_none());
+        assert!(formatter_by_name("json").is_none());
+    }
+}
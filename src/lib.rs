@@ -4,91 +4,500 @@
 
 use wasm_bindgen::prelude::wasm_bindgen;
 
+#[cfg(feature = "dlx")]
+mod dlx;
+mod format;
+mod parse;
 mod pattern;
+mod search;
 mod setup;
 mod template;
 
-pub use pattern::Pattern;
-pub use setup::{ImpossiblePuzzle, Possibilities};
-pub use template::{Solution, Template};
+#[cfg(feature = "dlx")]
+pub use dlx::solve_dlx;
+pub use format::{
+    formatter_by_name, CompactFormatter, GridFormatter, PencilmarkFormatter, PuzzleFormatter,
+};
+pub use parse::{iter_puzzles, parse, parse_labeled, ParseError};
+pub use pattern::{Orientation, Pattern, PatternParseError, Unit};
+pub use search::SearchTree;
+pub use setup::{
+    CandidateElimination, Checkpoint, ConstraintCounts, ImpossiblePuzzle, Possibilities,
+    PossibilitiesSnapshot, PropagationEvent, StepResult, Technique,
+};
+pub use template::{AnnotatedSolution, CellRender, Solution, Template};
 
 /// Prepare a puzzle from user input.
 pub fn prepare(input: &[[u8; 9]; 9]) -> Result<Possibilities, ImpossiblePuzzle> {
-    let mut puzzle = Possibilities::new();
+    Possibilities::try_from(*input)
+}
 
-    for row in 0..9 {
-        for col in 0..9 {
-            if input[row][col] > 0 {
-                puzzle.set(row as u8, col as u8, input[row][col])?;
+/// Why [`quick_reject`] rejected a puzzle.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum RejectReason {
+    /// A cell holds something other than 0 through 9.
+    BadDigit { row: usize, col: usize },
+    /// A digit is given more than once in a unit.
+    DuplicateClue { unit: Unit, digit: u8 },
+    /// A digit has nowhere left to go in a unit.
+    NoPlaceFor { unit: Unit, digit: u8 },
+    /// Too few clues for a unique solution.  Holds the number of clues.
+    TooFewClues(usize),
+}
+
+/// Look for obvious problems with a puzzle, without solving it.
+///
+/// When `require_unique` is set, puzzles with fewer than 17 clues are rejected:
+/// no such puzzle has a unique solution.
+///
+/// `None` doesn't mean the puzzle can be solved, only that nothing is
+/// obviously wrong.
+pub fn quick_reject(puzzle: &[[u8; 9]; 9], require_unique: bool) -> Option<RejectReason> {
+    let mut clues = [Pattern::EMPTY; 9];
+    for (row, digits) in puzzle.iter().enumerate() {
+        for (col, &digit) in digits.iter().enumerate() {
+            match digit {
+                0 => {}
+                1..=9 => clues[digit as usize - 1] = clues[digit as usize - 1].with(row, col),
+                _ => return Some(RejectReason::BadDigit { row, col }),
+            }
+        }
+    }
+    let all_clues = clues.iter().fold(Pattern::EMPTY, |acc, &p| acc | p);
+
+    let units = (0..9).flat_map(|i| [Unit::Row(i), Unit::Col(i), Unit::Box(i)]);
+
+    for (digit, &digit_clues) in clues.iter().enumerate() {
+        // empty cells that don't see this digit
+        let mut blocked = all_clues;
+        for (row, col) in digit_clues.cells() {
+            blocked = blocked | Pattern::peers(9 * row + col);
+        }
+        let available = !blocked;
+
+        for unit in units.clone() {
+            let given = (digit_clues & Pattern::unit_mask(unit)).count();
+            let digit = digit as u8 + 1;
+            if given > 1 {
+                return Some(RejectReason::DuplicateClue { unit, digit });
+            }
+            if given == 0 && !available.intersects(Pattern::unit_mask(unit)) {
+                return Some(RejectReason::NoPlaceFor { unit, digit });
             }
         }
     }
 
-    Ok(puzzle)
+    let count = all_clues.count() as usize;
+    if require_unique && count < 17 {
+        return Some(RejectReason::TooFewClues(count));
+    }
+
+    None
 }
 
 /// Solve a puzzle, stopping after a maximum number of solutions.
 #[wasm_bindgen]
 pub fn solve(puzzle: Vec<u8>, max_solutions: usize) -> Vec<String> {
+    solve_with_progress(puzzle, max_solutions, |_| {})
+}
+
+/// Like [`solve`], but write solutions with the named formatter.
+///
+/// See [`formatter_by_name`].  Unknown names give no solutions.
+#[wasm_bindgen]
+pub fn solve_formatted(puzzle: Vec<u8>, max_solutions: usize, format: &str) -> Vec<String> {
+    let Some(formatter) = formatter_by_name(format) else {
+        return Vec::new();
+    };
+    let Ok(possibilities) = prepare_flat(&puzzle) else {
+        return Vec::new(); // no solutions
+    };
+
+    SearchTree::new(&possibilities)
+        .max_solutions(max_solutions)
+        .run()
+        .iter()
+        .map(|s| formatter.format_solution(s))
+        .collect()
+}
+
+/// Like [`solve`], but periodically report an estimate of progress in `0.0..=1.0`.
+///
+/// Exact progress is impossible to know.  The estimate is the fraction of the
+/// first (outermost) digit's templates which have been explored.
+pub fn solve_with_progress(
+    puzzle: Vec<u8>,
+    max_solutions: usize,
+    mut progress: impl FnMut(f64),
+) -> Vec<String> {
     // Two-phase solving.
     //   1.  Typical logic; see [`Possibilities`].
     //   2.  Exhaustive search by digit; see [`Template`].
     // This seems to be a perfect balance between logic and brute force.
     // The logic pares down the search space very effectively.
 
+    let Ok(possibilities) = prepare_flat(&puzzle) else {
+        return Vec::new(); // no solutions
+    };
+
+    SearchTree::new(&possibilities)
+        .max_solutions(max_solutions)
+        .run_with_progress(&mut progress)
+        .into_iter()
+        .map(|s| format!("{}", s))
+        .collect()
+}
+
+/// Like [`solve`], but search digits in natural order instead of most- to
+/// least-restricted.
+///
+/// Solutions come out in the same relative order no matter which clues are
+/// given.  This can be much slower, especially for puzzles with few clues.
+#[wasm_bindgen]
+pub fn solve_stable(puzzle: Vec<u8>, max_solutions: usize) -> Vec<String> {
+    let Ok(possibilities) = prepare_flat(&puzzle) else {
+        return Vec::new(); // no solutions
+    };
+
+    SearchTree::from_templates(digit_templates(&possibilities))
+        .max_solutions(max_solutions)
+        .run()
+        .into_iter()
+        .map(|s| format!("{}", s))
+        .collect()
+}
+
+/// Prepare a puzzle from 81 cells in row-major order.
+fn prepare_flat(puzzle: &[u8]) -> Result<Possibilities, ImpossiblePuzzle> {
+    if puzzle.len() != 81 {
+        return Err(ImpossiblePuzzle); // not a puzzle at all
+    }
+
     let mut possibilities = Possibilities::new();
-    for cell in 0..81 {
-        if puzzle[cell as usize] == 0 {
+    for (cell, &digit) in puzzle.iter().enumerate() {
+        if digit == 0 {
             continue;
         }
 
-        if possibilities
-            .set(cell / 9, cell % 9, puzzle[cell as usize])
-            .is_err()
-        {
-            return Vec::new(); // no solutions
+        possibilities.set(cell / 9, cell % 9, digit)?;
+    }
+    Ok(possibilities)
+}
+
+/// Does the puzzle have exactly one solution?
+///
+/// Like checking for two solutions with [`SearchTree`], but templates are
+/// found with [`Template::within_fast`], and the search stops as soon as a
+/// second branch has room for the last digit, without placing it.
+pub fn is_unique_fast(puzzle: &Possibilities) -> bool {
+    let mut templates: [(usize, Vec<Template>); 9] = std::array::from_fn(|digit| {
+        (
+            digit,
+            Template::within_fast(puzzle.patterns()[digit]).collect(),
+        )
+    });
+    templates.sort_by_key(|(_digit, possible)| possible.len());
+
+    SearchTree::from_templates(templates).count_up_to(2) == 1
+}
+
+/// Outcome of [`solve_classify`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SolveResult {
+    /// No solutions.
+    Impossible,
+    /// Exactly one solution.
+    Unique(Solution),
+    /// More than one solution.  Holds at most `max_multiple` of them.
+    Multiple(Vec<Solution>),
+}
+
+/// Solve a puzzle and report whether it has zero, one, or many solutions.
+pub fn solve_classify(puzzle: &[[u8; 9]; 9], max_multiple: usize) -> SolveResult {
+    let Ok(possibilities) = prepare(puzzle) else {
+        return SolveResult::Impossible;
+    };
+
+    let mut solutions = SearchTree::new(&possibilities)
+        .max_solutions(max_multiple.max(2))
+        .run();
+    match solutions.len() {
+        0 => SolveResult::Impossible,
+        1 => SolveResult::Unique(solutions.pop().unwrap()),
+        _ => {
+            solutions.truncate(max_multiple);
+            SolveResult::Multiple(solutions)
         }
     }
+}
 
-    // Search digits from most- to least-restricted.
-    //   - If the puzzle has a unique solution then this order doesn't do much.
-    //   - If there are only a few clues, this makes it way faster.  :-)
-    //   - Downside: adding clues makes solution ordering unstable.  :-(
+/// Solve a puzzle by logic alone, recording every elimination and why.
+///
+/// Eliminations already made in `puzzle` aren't recorded.  The solution is
+/// `None` if logic doesn't finish the puzzle, since a guess has no reason to
+/// record.
+pub fn solve_with_log(puzzle: &Possibilities) -> (Option<Solution>, Vec<CandidateElimination>) {
+    let mut possibilities = puzzle.clone();
+    possibilities.record_eliminations();
 
-    let mut templates: [(usize, Vec<Template>); 9] = Default::default();
-    for digit in 0..9 {
-        templates[digit] = (
-            digit,
-            Template::within(possibilities.patterns[digit]).collect(),
-        );
+    let solved = match apply_logic(&mut possibilities) {
+        Ok(()) => possibilities.unique(),
+        Err(ImpossiblePuzzle) => None,
+    };
+    (solved, possibilities.take_eliminations())
+}
+
+/// Outcome of [`solve_logical`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum LogicalOutcome {
+    /// Logic found every digit.
+    Solved(Solution),
+    /// Logic made no more progress.  Finishing needs a guess.
+    Stuck(Box<Possibilities>),
+    /// The clues contradict each other.
+    Contradiction,
+}
+
+/// Solve a puzzle using logic techniques only, never searching.
+///
+/// A puzzle which comes out [`Stuck`](LogicalOutcome::Stuck) can't be
+/// finished by these techniques without guessing.
+pub fn solve_logical(puzzle: &[[u8; 9]; 9]) -> LogicalOutcome {
+    let Ok(mut possibilities) = prepare(puzzle) else {
+        return LogicalOutcome::Contradiction;
+    };
+    if apply_logic(&mut possibilities).is_err() {
+        return LogicalOutcome::Contradiction;
     }
-    templates.sort_by_key(|(_digit, possible)| possible.len());
 
-    let mut solutions = Vec::new();
-    let mut solution = Solution::default();
+    let solved = possibilities.cells_with_n_candidates(1).count() == 81;
+    match possibilities.unique() {
+        Some(solution) if solved => LogicalOutcome::Solved(solution),
+        _ => LogicalOutcome::Stuck(Box::new(possibilities)),
+    }
+}
+
+/// Apply logic techniques until none makes progress.
+fn apply_logic(possibilities: &mut Possibilities) -> Result<(), ImpossiblePuzzle> {
+    // Simplest technique first.
+    fn progress(p: &mut Possibilities) -> Result<bool, ImpossiblePuzzle> {
+        Ok(p.apply_naked_singles()?
+            || p.apply_hidden_singles()?
+            || p.apply_intersection_removal()?
+            || p.apply_naked_fish_general(2)?
+            || p.apply_naked_fish_general(3)?
+            || p.apply_naked_fish_general(4)?)
+    }
+    while progress(possibilities)? {}
+    Ok(())
+}
+
+/// Solve a puzzle, remembering which cells were clues.
+pub fn solve_annotated(puzzle: &[[u8; 9]; 9], max_solutions: usize) -> Vec<AnnotatedSolution> {
+    let Ok(possibilities) = prepare(puzzle) else {
+        return Vec::new();
+    };
+
+    let mut givens = Pattern::EMPTY;
+    for (row, digits) in puzzle.iter().enumerate() {
+        for (col, &digit) in digits.iter().enumerate() {
+            if digit > 0 {
+                givens = givens.with(row, col);
+            }
+        }
+    }
+
+    SearchTree::new(&possibilities)
+        .max_solutions(max_solutions)
+        .run()
+        .into_iter()
+        .map(|solution| AnnotatedSolution { solution, givens })
+        .collect()
+}
+
+/// Solve a puzzle after reorienting it, then map solutions back to the
+/// puzzle's original orientation.
+///
+/// Sudoku rules are symmetric, so this finds the same solutions as solving
+/// directly.  Only the order they're found in changes.
+pub fn solve_oriented(
+    puzzle: &[[u8; 9]; 9],
+    orientation: Orientation,
+    max_solutions: usize,
+) -> Vec<[[u8; 9]; 9]> {
+    fn reorient(grid: &[[u8; 9]; 9], orientation: Orientation) -> [[u8; 9]; 9] {
+        let mut out = [[0; 9]; 9];
+        for (row, digits) in grid.iter().enumerate() {
+            for (col, &digit) in digits.iter().enumerate() {
+                let (r, c) = orientation.apply(row, col);
+                out[r][c] = digit;
+            }
+        }
+        out
+    }
+
+    let Ok(possibilities) = prepare(&reorient(puzzle, orientation)) else {
+        return Vec::new();
+    };
+
+    SearchTree::new(&possibilities)
+        .max_solutions(max_solutions)
+        .run()
+        .into_iter()
+        .map(|solution| {
+            let mut grid = [[0; 9]; 9];
+            for (i, digit) in solution.to_grid().into_iter().enumerate() {
+                grid[i / 9][i % 9] = digit;
+            }
+            reorient(&grid, orientation.inverse())
+        })
+        .collect()
+}
+
+/// Error returned by [`solve_from_clues`] and [`completions`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum SolveError {
+    /// A clue is outside the grid, or its digit isn't 1 through 9.
+    OutOfRange { row: usize, col: usize, digit: u8 },
+    /// The clues contradict each other, or an entry contradicts a clue.
+    Conflict,
+}
+
+impl std::fmt::Display for SolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SolveError::OutOfRange { row, col, digit } => {
+                write!(f, "clue {} at ({}, {}) out of range", digit, row, col)
+            }
+            SolveError::Conflict => write!(f, "clues contradict each other"),
+        }
+    }
+}
+
+impl std::error::Error for SolveError {}
+
+/// Solve a puzzle given as `(row, col, digit)` clues, stopping after a
+/// maximum number of solutions.
+pub fn solve_from_clues(
+    clues: &[(usize, usize, u8)],
+    max_solutions: usize,
+) -> Result<Vec<Solution>, SolveError> {
+    for &(row, col, digit) in clues {
+        if row >= 9 || col >= 9 || !(1..=9).contains(&digit) {
+            return Err(SolveError::OutOfRange { row, col, digit });
+        }
+    }
+
+    let mut possibilities = Possibilities::new();
+    possibilities
+        .set_batch(clues)
+        .map_err(|ImpossiblePuzzle| SolveError::Conflict)?;
+
+    Ok(SearchTree::new(&possibilities)
+        .max_solutions(max_solutions)
+        .run())
+}
+
+/// Number of ways to finish a grid from the original `clues` plus a
+/// player's partial `filled` entries, counting at most `cap`.
+///
+/// Empty cells are 0 in both grids.  Entries which break the rules only
+/// leave no way to finish; an entry which disagrees with a clue is an error.
+pub fn completions(
+    clues: &[[u8; 9]; 9],
+    filled: &[[u8; 9]; 9],
+    cap: usize,
+) -> Result<usize, SolveError> {
+    let mut merged = *clues;
+    for row in 0..9 {
+        for col in 0..9 {
+            let (clue, entry) = (clues[row][col], filled[row][col]);
+            if clue > 9 || entry > 9 {
+                let digit = clue.max(entry);
+                return Err(SolveError::OutOfRange { row, col, digit });
+            }
+            match (clue, entry) {
+                (_, 0) => {}
+                (0, _) => merged[row][col] = entry,
+                _ if clue != entry => return Err(SolveError::Conflict),
+                _ => {}
+            }
+        }
+    }
+
+    if cap == 0 {
+        return Ok(0);
+    }
+    let Ok(possibilities) = prepare(&merged) else {
+        return Ok(0);
+    };
+
+    let mut count = 0;
+    SearchTree::new(&possibilities).for_each(&mut |_solution| {
+        count += 1;
+        count < cap
+    });
+    Ok(count)
+}
+
+/// Where [`solve_paged`] left off.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Cursor {
+    /// Index into each digit's template list of the last solution returned
+    path: Vec<usize>,
+}
 
+/// Solve a puzzle a page at a time.
+///
+/// Returns up to `page_size` solutions after `cursor` (or from the start), and
+/// a cursor to pass back in for the next page.  The cursor is `None` once
+/// every solution has been returned.  A cursor is only meaningful for the
+/// puzzle that produced it.
+pub fn solve_paged(
+    puzzle: &[[u8; 9]; 9],
+    cursor: Option<&Cursor>,
+    page_size: usize,
+) -> (Vec<Solution>, Option<Cursor>) {
+    if page_size == 0 {
+        return (Vec::new(), cursor.cloned());
+    }
+    let Ok(possibilities) = prepare(puzzle) else {
+        return (Vec::new(), None);
+    };
+    let templates = Template::sorted_by_restriction(possibilities.patterns());
+
+    // Like `SearchTree`, but tracks the path to each solution.
+    // While following `start`, every level begins at the cursor's index, and
+    // the solution at the very end of the path was already returned.
     fn search(
-        out: &mut Vec<Solution>,
+        out: &mut Vec<(Solution, Vec<usize>)>,
         solution: &mut Solution,
         filled: Pattern,
         templates: &[(usize, Vec<Template>)],
-        max_solutions: usize,
+        path: &mut Vec<usize>,
+        start: Option<&[usize]>,
+        limit: usize,
     ) {
         match templates.split_first() {
-            None => out.push(solution.clone()),
+            None if start.is_some() => {} // returned in an earlier page
+            None => out.push((solution.clone(), path.clone())),
 
             Some(((digit, possible), rest)) => {
-                for &template in possible {
+                let first = start.map_or(0, |start| start[0]);
+                for (i, &template) in possible.iter().enumerate().skip(first) {
                     if template.as_pattern().intersects(filled) {
                         continue;
                     }
 
                     solution.0[*digit] = template;
+                    path.push(i);
 
                     let filled = filled | template.as_pattern();
-                    search(out, solution, filled, rest, max_solutions);
+                    let start = start.filter(|_| i == first).map(|start| &start[1..]);
+                    search(out, solution, filled, rest, path, start, limit);
+                    path.pop();
 
-                    if out.len() >= max_solutions {
+                    if out.len() >= limit {
                         return;
                     }
                 }
@@ -96,15 +505,492 @@ pub fn solve(puzzle: Vec<u8>, max_solutions: usize) -> Vec<String> {
         }
     }
 
-    // web_sys::console::time_with_label("solution search");
+    // Look one solution past the page, to know whether there are more.
+    let mut found = Vec::new();
     search(
-        &mut solutions,
-        &mut solution,
+        &mut found,
+        &mut Solution::default(),
         Pattern::EMPTY,
         &templates,
-        max_solutions,
+        &mut Vec::new(),
+        cursor.map(|c| c.path.as_slice()),
+        page_size.saturating_add(1),
     );
-    // web_sys::console::time_end_with_label("solution search");
 
-    solutions.into_iter().map(|s| format!("{}", s)).collect()
+    let more = found.len() > page_size;
+    found.truncate(page_size);
+    let cursor = found
+        .last()
+        .filter(|_| more)
+        .map(|(_, path)| Cursor { path: path.clone() });
+    (
+        found.into_iter().map(|(solution, _)| solution).collect(),
+        cursor,
+    )
+}
+
+/// Solve a puzzle, writing solutions into `out` instead of allocating.
+///
+/// Stops after `max_solutions` or `out.len()` solutions, whichever is fewer,
+/// and returns how many were written.  Only the per-digit template lists are
+/// allocated.
+pub fn solve_into(puzzle: &[[u8; 9]; 9], out: &mut [Solution], max_solutions: usize) -> usize {
+    let limit = max_solutions.min(out.len());
+    if limit == 0 {
+        return 0;
+    }
+    let Ok(possibilities) = prepare(puzzle) else {
+        return 0;
+    };
+
+    let mut written = 0;
+    SearchTree::new(&possibilities).for_each(&mut |solution| {
+        out[written] = solution.clone();
+        written += 1;
+        written < limit
+    });
+    written
+}
+
+/// Solve a puzzle, returning solutions nearest `reference` first.
+///
+/// Nearness is the number of cells which differ; see [`Solution::diff`].
+/// Only the first `max_solutions` solutions found are sorted.
+pub fn solve_nearest(
+    puzzle: &[[u8; 9]; 9],
+    max_solutions: usize,
+    reference: &Solution,
+) -> Vec<Solution> {
+    let Ok(possibilities) = prepare(puzzle) else {
+        return Vec::new();
+    };
+
+    let mut solutions = SearchTree::new(&possibilities)
+        .max_solutions(max_solutions)
+        .run();
+    solutions.sort_by_cached_key(|s| s.diff(reference).len());
+    solutions
+}
+
+/// Solve a puzzle, trying placements that overlap `preference` first.
+///
+/// Each digit's templates are tried in order of how many cells they share
+/// with that digit's preferred [`Pattern`].  This steers which solutions are
+/// found first, not which solutions exist.
+pub fn solve_preferring(
+    puzzle: &[[u8; 9]; 9],
+    max_solutions: usize,
+    preference: &[Pattern; 9],
+) -> Vec<Solution> {
+    let Ok(possibilities) = prepare(puzzle) else {
+        return Vec::new();
+    };
+
+    let mut templates = Template::sorted_by_restriction(possibilities.patterns());
+    for (digit, possible) in templates.iter_mut() {
+        let preferred = preference[*digit];
+        possible.sort_by_key(|t| std::cmp::Reverse((t.as_pattern() & preferred).count()));
+    }
+    SearchTree::from_templates(templates)
+        .max_solutions(max_solutions)
+        .run()
+}
+
+/// Solve a puzzle, trying each digit's templates in order of `key`.
+///
+/// Digits are still searched from most- to least-restricted.  This changes
+/// which solutions are found first, not which exist, but a poor ordering can
+/// make finding them much slower.
+pub fn solve_ordered<K: Ord>(
+    puzzle: &[[u8; 9]; 9],
+    max_solutions: usize,
+    key: impl Fn(&Template) -> K,
+) -> Vec<Solution> {
+    let Ok(possibilities) = prepare(puzzle) else {
+        return Vec::new();
+    };
+
+    let mut templates = Template::sorted_by_restriction(possibilities.patterns());
+    for (_digit, possible) in templates.iter_mut() {
+        possible.sort_by_cached_key(&key);
+    }
+    SearchTree::from_templates(templates)
+        .max_solutions(max_solutions)
+        .run()
+}
+
+/// Solve a puzzle, trying placements spread evenly within bands and stacks
+/// first.
+///
+/// Natural order finds lopsided grids first: the first template puts the digit
+/// in the same column of every box in a band.  Here each digit's templates are
+/// tried in order of how many different columns within a box they use in each
+/// band, plus how many different rows within a box they use in each stack.
+/// This changes which solutions are found first, not which exist.
+pub fn solve_balanced(puzzle: &[[u8; 9]; 9], max_solutions: usize) -> Vec<Solution> {
+    solve_ordered(puzzle, max_solutions, |&t| std::cmp::Reverse(spread(t)))
+}
+
+/// Evenness of a template for [`solve_balanced`], from 6 to 18.
+fn spread(template: Template) -> u32 {
+    let mut band_cols = [0u8; 3];
+    let mut stack_rows = [0u8; 3];
+    for (row, col) in template.as_pattern().cells() {
+        band_cols[row / 3] |= 1 << (col % 3);
+        stack_rows[col / 3] |= 1 << (row % 3);
+    }
+    (band_cols.iter().chain(&stack_rows))
+        .map(|bits| bits.count_ones())
+        .sum()
+}
+
+/// Templates possible for each digit, in digit order.
+fn digit_templates(possibilities: &Possibilities) -> [(usize, Vec<Template>); 9] {
+    std::array::from_fn(|digit| {
+        let possible = possibilities.patterns()[digit];
+        (digit, Template::within(possible).collect())
+    })
+}
+
+/// Small deterministic random index generator, so tests don't need a
+/// dependency.  `rng(n)` returns an index in `0..n`.
+#[cfg(test)]
+fn test_rng(seed: u64) -> impl FnMut(usize) -> usize {
+    let mut state = seed.max(1);
+    move |n| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state % n as u64) as usize
+    }
+}
+
+/// Pattern with each cell set with probability `percent / 100`.
+#[cfg(test)]
+fn test_pattern(rng: &mut impl FnMut(usize) -> usize, percent: usize) -> Pattern {
+    (0..81)
+        .filter(|_| rng(100) < percent)
+        .fold(Pattern::EMPTY, |p, i| p.with(i / 9, i % 9))
+}
+
+/// Minimal JSON reader, for checking output in tests.  Numbers must be
+/// whole, and strings can't have escapes.
+#[cfg(test)]
+mod json {
+    #[derive(Debug, PartialEq)]
+    pub enum Value {
+        Number(u64),
+        String(String),
+        Array(Vec<Value>),
+        Object(Vec<(String, Value)>),
+    }
+
+    /// The value, if `input` is exactly one JSON value.
+    pub fn parse(input: &str) -> Option<Value> {
+        let (value, rest) = value(input.trim_start())?;
+        rest.trim().is_empty().then_some(value)
+    }
+
+    fn value(input: &str) -> Option<(Value, &str)> {
+        match input.chars().next()? {
+            '[' => {
+                let mut items = Vec::new();
+                let mut rest = input[1..].trim_start();
+                if let Some(rest) = rest.strip_prefix(']') {
+                    return Some((Value::Array(items), rest));
+                }
+                loop {
+                    let (item, after) = value(rest)?;
+                    items.push(item);
+                    let after = after.trim_start();
+                    match after.chars().next()? {
+                        ',' => rest = after[1..].trim_start(),
+                        ']' => return Some((Value::Array(items), &after[1..])),
+                        _ => return None,
+                    }
+                }
+            }
+            '{' => {
+                let mut fields = Vec::new();
+                let mut rest = input[1..].trim_start();
+                if let Some(rest) = rest.strip_prefix('}') {
+                    return Some((Value::Object(fields), rest));
+                }
+                loop {
+                    let (Value::String(key), after) = value(rest)? else {
+                        return None;
+                    };
+                    let after = after.trim_start().strip_prefix(':')?.trim_start();
+                    let (item, after) = value(after)?;
+                    fields.push((key, item));
+                    let after = after.trim_start();
+                    match after.chars().next()? {
+                        ',' => rest = after[1..].trim_start(),
+                        '}' => return Some((Value::Object(fields), &after[1..])),
+                        _ => return None,
+                    }
+                }
+            }
+            '"' => {
+                let end = input[1..].find(['"', '\\'])? + 1;
+                (&input[end..end + 1] == "\"")
+                    .then(|| (Value::String(input[1..end].to_string()), &input[end + 1..]))
+            }
+            '0'..='9' => {
+                let end = input
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(input.len());
+                Some((Value::Number(input[..end].parse().ok()?), &input[end..]))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EASY: &str =
+        "53..7....6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79";
+
+    #[test]
+    fn is_unique_fast_matches_counting() {
+        let hard =
+            "4.....8.5.3..........7......2.....6.....8.4......1.......6.3.7.5..2.....1.4......";
+        let two =
+            ".........6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79";
+        for input in [EASY, hard, two, &".".repeat(81)] {
+            let puzzle = Possibilities::try_from(input).unwrap();
+            let count = SearchTree::new(&puzzle).max_solutions(2).run().len();
+            assert_eq!(is_unique_fast(&puzzle), count == 1, "{}", input);
+        }
+    }
+
+    #[test]
+    fn solve_with_log_eliminates_only_wrong_digits() {
+        let mut clued = Possibilities::new();
+        for (row, col, digit) in Possibilities::try_from(EASY).unwrap().clues() {
+            clued.set_deferred(row, col, digit);
+        }
+
+        let (solution, log) = solve_with_log(&clued);
+        let grid = <[[u8; 9]; 9]>::from(&solution.unwrap());
+        assert!(!log.is_empty());
+        for entry in log {
+            assert_ne!(grid[entry.row][entry.col], entry.digit);
+        }
+    }
+
+    #[test]
+    fn json_reader_rejects_bad_input() {
+        for bad in [
+            "",
+            "[1,2",
+            "[1,]",
+            "{\"a\" 1}",
+            "{1:2}",
+            "[1] 2",
+            "\"a\\\"b\"",
+        ] {
+            assert_eq!(json::parse(bad), None, "{}", bad);
+        }
+        assert!(json::parse(" { \"a\" : [ ] , \"b\" : [1, [2]] } ").is_some());
+    }
+
+    #[test]
+    fn solve_paged_covers_every_solution_once() {
+        let puzzle = parse(
+            ".8..46..9.1..794....9..3..2...7..95.....8...4.7....6.88..65.1.33.....84..964.8..7",
+        )
+        .unwrap();
+        let total = completions(&puzzle, &[[0; 9]; 9], usize::MAX).unwrap();
+        assert_eq!(total, 6);
+        let all = SearchTree::new(&prepare(&puzzle).unwrap()).run();
+
+        for page_size in 1..=7 {
+            let mut pages = Vec::new();
+            let mut cursor = None;
+            loop {
+                let (page, next) = solve_paged(&puzzle, cursor.as_ref(), page_size);
+                assert!(page.len() <= page_size);
+                assert!(!page.is_empty(), "empty page of size {}", page_size);
+                pages.extend(page);
+                match next {
+                    Some(next) => cursor = Some(next),
+                    None => break,
+                }
+            }
+            assert_eq!(pages.len(), total, "page size {}", page_size);
+            assert_eq!(pages, all, "page size {}", page_size);
+        }
+    }
+
+    #[test]
+    fn solve_classify_reaches_every_outcome() {
+        let solved = Solution::try_from(
+            "534678912672195348198342567859761423426853791713924856961537284287419635345286179",
+        )
+        .unwrap();
+        assert_eq!(
+            solve_classify(&parse(EASY).unwrap(), 5),
+            SolveResult::Unique(solved)
+        );
+
+        let two = parse(
+            ".........6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79",
+        )
+        .unwrap();
+        let SolveResult::Multiple(solutions) = solve_classify(&two, 5) else {
+            panic!("expected multiple solutions");
+        };
+        assert_eq!(solutions.len(), 2);
+        assert_ne!(solutions[0], solutions[1]);
+        let SolveResult::Multiple(solutions) = solve_classify(&two, 1) else {
+            panic!("expected multiple solutions");
+        };
+        assert_eq!(solutions.len(), 1);
+
+        // two 5s in the first row
+        let mut conflict = parse(EASY).unwrap();
+        conflict[0][8] = 5;
+        assert_eq!(solve_classify(&conflict, 5), SolveResult::Impossible);
+    }
+
+    #[test]
+    fn solve_stable_is_repeatable() {
+        let easy = parse(EASY).unwrap().concat();
+        assert_eq!(solve_stable(easy.clone(), 2), solve(easy.clone(), 2));
+        assert_eq!(solve_stable(easy, 2).len(), 1);
+
+        let two = parse(
+            ".........6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79",
+        )
+        .unwrap()
+        .concat();
+        let first = solve_stable(two.clone(), 10);
+        assert_eq!(first.len(), 2);
+        for _ in 0..3 {
+            assert_eq!(solve_stable(two.clone(), 10), first);
+        }
+        let mut sorted = first.clone();
+        let mut native = solve(two.clone(), 10);
+        sorted.sort();
+        native.sort();
+        assert_eq!(sorted, native);
+
+        // with a clue removed, the same solutions come in the same order
+        let mut fewer = two;
+        fewer[31] = 0;
+        let more = solve_stable(fewer, 10);
+        assert_eq!(more.len(), 6);
+        let mut rest = more.iter();
+        assert!(first.iter().all(|s| rest.any(|m| m == s)));
+    }
+
+    #[test]
+    fn quick_reject_reasons() {
+        let easy = parse(EASY).unwrap();
+        assert_eq!(quick_reject(&easy, true), None);
+
+        let mut bad = easy;
+        bad[2][3] = 10;
+        assert_eq!(
+            quick_reject(&bad, false),
+            Some(RejectReason::BadDigit { row: 2, col: 3 })
+        );
+
+        let mut duplicate = easy;
+        duplicate[0][8] = 5;
+        assert_eq!(
+            quick_reject(&duplicate, false),
+            Some(RejectReason::DuplicateClue {
+                unit: Unit::Row(0),
+                digit: 5
+            })
+        );
+
+        // 1 is ruled out of the only empty cell in the first row
+        let mut blocked = [[0; 9]; 9];
+        blocked[0] = [2, 3, 4, 5, 6, 7, 8, 9, 0];
+        blocked[4][8] = 1;
+        assert_eq!(
+            quick_reject(&blocked, false),
+            Some(RejectReason::NoPlaceFor {
+                unit: Unit::Row(0),
+                digit: 1
+            })
+        );
+
+        let mut sixteen = parse(
+            "4.....8.5.3..........7......2.....6.....8.4......1.......6.3.7.5..2.....1.4......",
+        )
+        .unwrap();
+        assert_eq!(quick_reject(&sixteen, true), None);
+        sixteen[0][0] = 0;
+        assert_eq!(
+            quick_reject(&sixteen, true),
+            Some(RejectReason::TooFewClues(16))
+        );
+        assert_eq!(quick_reject(&sixteen, false), None);
+    }
+
+    #[test]
+    fn completions_of_a_two_solution_puzzle() {
+        let two = parse(
+            ".........6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79",
+        )
+        .unwrap();
+        let none = [[0; 9]; 9];
+        assert_eq!(completions(&two, &none, 10), Ok(2));
+        assert_eq!(completions(&two, &none, 2), Ok(2));
+        assert_eq!(completions(&two, &none, 1), Ok(1));
+        assert_eq!(completions(&two, &none, 0), Ok(0));
+
+        // an entry from one solution leaves only that one
+        let solutions = SearchTree::new(&prepare(&two).unwrap()).run();
+        let grids: Vec<[[u8; 9]; 9]> = solutions.iter().map(<[[u8; 9]; 9]>::from).collect();
+        let (row, col) = (0..81)
+            .map(|i| (i / 9, i % 9))
+            .find(|&(row, col)| grids[0][row][col] != grids[1][row][col])
+            .unwrap();
+        let mut filled = none;
+        filled[row][col] = grids[0][row][col];
+        assert_eq!(completions(&two, &filled, 10), Ok(1));
+
+        // an entry breaking the rules, and one disagreeing with a clue
+        let mut filled = none;
+        filled[1][1] = 6;
+        assert_eq!(completions(&two, &filled, 10), Ok(0));
+        let mut filled = none;
+        filled[1][0] = 7;
+        assert_eq!(completions(&two, &filled, 10), Err(SolveError::Conflict));
+    }
+
+    #[test]
+    fn solve_logical_outcomes() {
+        let easy = parse(EASY).unwrap();
+        let LogicalOutcome::Solved(solution) = solve_logical(&easy) else {
+            panic!("singles solve this one");
+        };
+        assert_eq!(
+            solution.to_string(),
+            "534678912672195348198342567859761423426853791713924856961537284287419635345286179"
+        );
+
+        // two solutions, so no logic can finish it
+        let two = parse(
+            ".........6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79",
+        )
+        .unwrap();
+        let LogicalOutcome::Stuck(stuck) = solve_logical(&two) else {
+            panic!("expected to get stuck");
+        };
+        assert!(stuck.cells_with_n_candidates(1).count() < 81);
+        assert_eq!(SearchTree::new(&stuck).run().len(), 2);
+
+        let mut conflict = easy;
+        conflict[0][8] = 5;
+        assert_eq!(solve_logical(&conflict), LogicalOutcome::Contradiction);
+    }
 }
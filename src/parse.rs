@@ -0,0 +1,222 @@
+/// Error returned when reading a puzzle from text fails.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ParseError {
+    /// The input didn't contain exactly 81 cells.  Holds the number found.
+    WrongLength(usize),
+    /// The clues contradict each other.
+    Impossible,
+    /// Reading the input failed.
+    Io(std::io::ErrorKind),
+}
+
+impl From<crate::ImpossiblePuzzle> for ParseError {
+    fn from(_: crate::ImpossiblePuzzle) -> ParseError {
+        ParseError::Impossible
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::WrongLength(n) => write!(f, "expected 81 cells, found {}", n),
+            ParseError::Impossible => write!(f, "clues contradict each other"),
+            ParseError::Io(kind) => write!(f, "read failed: {}", kind),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Read a puzzle from text, leniently.
+///
+/// Digits `1` through `9` are clues; `0` and `.` are empty cells.  Everything
+/// else (whitespace, grid lines, and so on) is ignored.
+pub fn parse(input: &str) -> Result<[[u8; 9]; 9], ParseError> {
+    let cells: Vec<u8> = input.chars().filter_map(cell_value).collect();
+    to_grid(cells)
+}
+
+/// Like [`parse`], but first strip axis labels, as some books and websites
+/// print them.
+///
+/// The column labels are a first line reading exactly `123456789`, and the
+/// row labels are the first digit of each following line, reading `1`
+/// through `9` in order.  Either may be missing.  Lines without any cells
+/// are ignored.
+pub fn parse_labeled(input: &str) -> Result<[[u8; 9]; 9], ParseError> {
+    let mut lines: Vec<Vec<u8>> = input
+        .lines()
+        .map(|line| line.chars().filter_map(cell_value).collect::<Vec<u8>>())
+        .filter(|cells| !cells.is_empty())
+        .collect();
+
+    // Without row labels, a first row reading 123456789 might be clues.
+    let labels: Vec<u8> = (1..=9).collect();
+    if lines.len() == 10 && lines[0] == labels {
+        lines.remove(0);
+    }
+
+    let row_labeled = lines.len() == 9
+        && (lines.iter().zip(1..)).all(|(cells, label)| cells.len() == 10 && cells[0] == label);
+    if row_labeled {
+        for cells in &mut lines {
+            cells.remove(0);
+        }
+    }
+
+    to_grid(lines.concat())
+}
+
+/// Read the grid of a Sudoku Exchange line, as written by
+/// [`exchange_format`](crate::format::exchange_format).
+///
+/// The grid is the first whitespace-separated field of exactly 81 digits;
+/// other fields (hash, rating, and so on) are ignored.
+pub(crate) fn parse_exchange(input: &str) -> Result<[[u8; 9]; 9], ParseError> {
+    let digit_fields = input
+        .split_whitespace()
+        .filter(|field| field.bytes().all(|b| b.is_ascii_digit()));
+    match digit_fields.clone().find(|field| field.len() == 81) {
+        Some(field) => to_grid(field.bytes().map(|b| b - b'0').collect()),
+        // report the field most likely meant as the grid
+        None => Err(ParseError::WrongLength(
+            digit_fields.map(str::len).max().unwrap_or(0),
+        )),
+    }
+}
+
+fn cell_value(c: char) -> Option<u8> {
+    match c {
+        '.' => Some(0),
+        '0'..='9' => Some(c as u8 - b'0'),
+        _ => None,
+    }
+}
+
+fn to_grid(cells: Vec<u8>) -> Result<[[u8; 9]; 9], ParseError> {
+    if cells.len() != 81 {
+        return Err(ParseError::WrongLength(cells.len()));
+    }
+
+    let mut grid = [[0; 9]; 9];
+    for (i, cell) in cells.into_iter().enumerate() {
+        grid[i / 9][i % 9] = cell;
+    }
+    Ok(grid)
+}
+
+/// Read one puzzle per line, lazily, as [`parse`] would.  Blank lines are
+/// skipped.
+///
+/// An I/O error is yielded as [`ParseError::Io`], and ends the iteration.
+pub fn iter_puzzles(
+    reader: impl std::io::BufRead,
+) -> impl Iterator<Item = Result<crate::Possibilities, ParseError>> {
+    let mut failed = false;
+    reader
+        .lines()
+        .map_while(move |line| match (failed, line) {
+            (true, _) => None,
+            (false, Ok(line)) => Some(Ok(line)),
+            (false, Err(error)) => {
+                failed = true;
+                Some(Err(ParseError::Io(error.kind())))
+            }
+        })
+        .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+        .map(|line| crate::Possibilities::try_from(line?.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exchange_length_error_counts_grid_digits() {
+        let short = "1".repeat(80);
+        let line = format!("{} 1234abcd 5.6", short);
+        assert_eq!(parse_exchange(&line), Err(ParseError::WrongLength(80)));
+        assert_eq!(parse_exchange("x y"), Err(ParseError::WrongLength(0)));
+    }
+
+    /// Reader which fails after its contents.
+    struct Truncated(&'static [u8]);
+
+    impl std::io::Read for Truncated {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.0.is_empty() {
+                return Err(std::io::ErrorKind::UnexpectedEof.into());
+            }
+            let n = self.0.len().min(buf.len());
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn iter_puzzles_skips_blank_lines() {
+        let empty = ".".repeat(81);
+        let input = format!("{}\n\n   \n{}\n\n", empty, empty);
+        let puzzles: Vec<_> = iter_puzzles(input.as_bytes()).collect();
+        assert_eq!(puzzles.len(), 2);
+        assert!(puzzles.iter().all(Result::is_ok));
+    }
+
+    #[test]
+    fn iter_puzzles_yields_io_errors() {
+        let reader = std::io::BufReader::new(Truncated(b"123\n"));
+        let puzzles: Vec<_> = iter_puzzles(reader).map(|p| p.err()).collect();
+        assert_eq!(
+            puzzles,
+            [
+                Some(ParseError::WrongLength(3)),
+                Some(ParseError::Io(std::io::ErrorKind::UnexpectedEof)),
+            ]
+        );
+    }
+
+    const EASY: &str =
+        "53..7....6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79";
+
+    /// The puzzle as nine lines, each prefixed by `label(row)`.
+    fn lines(label: impl Fn(usize) -> String) -> String {
+        (0..9)
+            .map(|row| format!("{}{}\n", label(row), &EASY[9 * row..9 * row + 9]))
+            .collect()
+    }
+
+    #[test]
+    fn parse_labeled_strips_labels() {
+        let expected = parse(EASY).unwrap();
+
+        let labeled = format!("  123456789\n{}", lines(|row| format!("{} ", row + 1)));
+        assert_eq!(parse_labeled(&labeled), Ok(expected));
+
+        // only one kind of label
+        let rows_only = lines(|row| format!("{}|", row + 1));
+        assert_eq!(parse_labeled(&rows_only), Ok(expected));
+        let cols_only = format!("123456789\n\n{}", lines(|_| String::new()));
+        assert_eq!(parse_labeled(&cols_only), Ok(expected));
+    }
+
+    #[test]
+    fn parse_labeled_without_labels() {
+        let expected = parse(EASY).unwrap();
+        assert_eq!(parse_labeled(EASY), Ok(expected));
+        assert_eq!(parse_labeled(&lines(|_| String::new())), Ok(expected));
+
+        // a first row of 123456789 is clues, not labels
+        let clues = format!("123456789\n{}", ".........\n".repeat(8));
+        assert_eq!(parse_labeled(&clues), parse(&clues));
+    }
+
+    #[test]
+    fn parse_labeled_wrong_length() {
+        assert_eq!(parse_labeled(&EASY[..80]), Err(ParseError::WrongLength(80)));
+
+        // with a tenth labeled row, no labels are stripped
+        let extra = lines(|row| format!("{} ", row + 1)) + "1" + &EASY[..9];
+        assert_eq!(parse_labeled(&extra), Err(ParseError::WrongLength(100)));
+    }
+}
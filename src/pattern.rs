@@ -1,4 +1,5 @@
-use std::ops::{BitAnd, BitOr, Not};
+use std::ops::{BitAnd, BitOr, BitXor, Not};
+use std::sync::OnceLock;
 
 /// Bit field of Sudoku cells.
 ///
@@ -7,10 +8,82 @@ use std::ops::{BitAnd, BitOr, Not};
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct Pattern(pub [u32; 3]);
 
+/// Error returned when reading a [`Pattern`] from text fails.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PatternParseError;
+
+impl std::fmt::Display for PatternParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid pattern")
+    }
+}
+
+impl std::error::Error for PatternParseError {}
+
+/// A row, column, or box of the grid.  Boxes are indexed row-major, like cells.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Unit {
+    Row(usize),
+    Col(usize),
+    Box(usize),
+}
+
+/// One of the eight symmetries of the square grid.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Orientation {
+    Identity,
+    /// Quarter turn clockwise.
+    Rotate90,
+    Rotate180,
+    /// Quarter turn counterclockwise.
+    Rotate270,
+    /// Mirror left to right.
+    FlipHorizontal,
+    /// Mirror top to bottom.
+    FlipVertical,
+    /// Mirror across the main diagonal.
+    Transpose,
+    /// Mirror across the other diagonal.
+    AntiTranspose,
+}
+
+impl Orientation {
+    /// Where the cell ends up.
+    pub fn apply(self, row: usize, col: usize) -> (usize, usize) {
+        match self {
+            Orientation::Identity => (row, col),
+            Orientation::Rotate90 => (col, 8 - row),
+            Orientation::Rotate180 => (8 - row, 8 - col),
+            Orientation::Rotate270 => (8 - col, row),
+            Orientation::FlipHorizontal => (row, 8 - col),
+            Orientation::FlipVertical => (8 - row, col),
+            Orientation::Transpose => (col, row),
+            Orientation::AntiTranspose => (8 - col, 8 - row),
+        }
+    }
+
+    /// Orientation which undoes this one.
+    pub fn inverse(self) -> Orientation {
+        match self {
+            Orientation::Rotate90 => Orientation::Rotate270,
+            Orientation::Rotate270 => Orientation::Rotate90,
+            other => other,
+        }
+    }
+}
+
 impl Pattern {
     pub const EMPTY: Pattern = Pattern([0, 0, 0]);
     pub const FULL: Pattern = Pattern([0xFFFFFFFF, 0xFFFFFFFF, 0x1FFFF]);
 
+    /// Pattern containing only the given cell.  Usable in `const` items.
+    pub const fn bit(row: usize, col: usize) -> Pattern {
+        let idx = 9 * row + col;
+        let mut words = [0; 3];
+        words[idx / 32] = 1 << (idx % 32);
+        Pattern(words)
+    }
+
     /// Does the pattern contain the cell?
     pub fn has(self, row: usize, col: usize) -> bool {
         let idx = 9 * row + col;
@@ -28,12 +101,211 @@ impl Pattern {
     /// New pattern also containing the given cell.
     #[must_use]
     #[allow(unused_parens)]
-    pub fn with(mut self, row: usize, col: usize) -> Pattern {
+    pub const fn with(mut self, row: usize, col: usize) -> Pattern {
         let idx = 9 * row + col;
         self.0[idx / 32] |= (1 << (idx % 32));
         self
     }
 
+    /// Compact 21-digit hexadecimal form, for debugging and snapshots.
+    pub fn fmt_hex(&self) -> String {
+        format!("{:08X}{:08X}{:05X}", self.0[0], self.0[1], self.0[2])
+    }
+
+    /// 81 characters in row-major order, `'1'` for each cell in the pattern
+    /// and `'0'` for the rest.
+    pub fn fmt_compact(&self) -> String {
+        (0..81)
+            .map(|i| if self.has(i / 9, i % 9) { '1' } else { '0' })
+            .collect()
+    }
+
+    /// Read the form written by [`Pattern::fmt_hex`].
+    pub fn from_hex(s: &str) -> Result<Pattern, PatternParseError> {
+        if s.len() != 21 || !s.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(PatternParseError);
+        }
+        let word = |range| u32::from_str_radix(&s[range], 16).map_err(|_| PatternParseError);
+        let pattern = Pattern([word(0..8)?, word(8..16)?, word(16..21)?]);
+        if pattern.0[2] > 0x1FFFF {
+            return Err(PatternParseError);
+        }
+        Ok(pattern)
+    }
+
+    /// Cells as space-separated `r{row}c{col}` tokens, 1-indexed:  `"r1c1 r2c4"`.
+    pub fn to_sudoku_line_notation(self) -> String {
+        let tokens: Vec<String> = self
+            .cells()
+            .map(|(row, col)| format!("r{}c{}", row + 1, col + 1))
+            .collect();
+        tokens.join(" ")
+    }
+
+    /// Read the form written by [`Pattern::to_sudoku_line_notation`].  Tokens
+    /// may be in any order.
+    pub fn from_sudoku_line_notation(s: &str) -> Result<Pattern, PatternParseError> {
+        let coordinate = |digit: &str| match digit.as_bytes() {
+            [b @ b'1'..=b'9'] => Ok((b - b'1') as usize),
+            _ => Err(PatternParseError),
+        };
+
+        let mut pattern = Pattern::EMPTY;
+        for token in s.split_whitespace() {
+            let (row, col) = token
+                .strip_prefix('r')
+                .and_then(|rest| rest.split_once('c'))
+                .ok_or(PatternParseError)?;
+            pattern = pattern.with(coordinate(row)?, coordinate(col)?);
+        }
+        Ok(pattern)
+    }
+
+    /// Row and column of every cell in the pattern, row-major.
+    pub fn cells(self) -> impl Iterator<Item = (usize, usize)> {
+        (0..81)
+            .map(|i| (i / 9, i % 9))
+            .filter(move |&(row, col)| self.has(row, col))
+    }
+
+    /// Number of cells in the pattern.
+    pub fn count(self) -> u32 {
+        self.0.iter().map(|bits| bits.count_ones()).sum()
+    }
+
+    /// Pattern with the cells of each row given as a column bit mask.  Bit
+    /// `c` of `rows[r]` is the cell at row `r`, column `c`; bits above 8 are
+    /// ignored.
+    pub fn from_row_bits(rows: &[u16; 9]) -> Pattern {
+        let mut pattern = Pattern::EMPTY;
+        for (row, bits) in rows.iter().enumerate() {
+            for col in (0..9).filter(|col| bits & (1 << col) != 0) {
+                pattern = pattern.with(row, col);
+            }
+        }
+        pattern
+    }
+
+    /// Each row as a column bit mask, the reverse of [`Pattern::from_row_bits`].
+    pub fn to_row_bits(self) -> [u16; 9] {
+        let mut rows = [0; 9];
+        for (row, col) in self.cells() {
+            rows[row] |= 1 << col;
+        }
+        rows
+    }
+
+    /// Number of cells in each row.
+    pub fn cells_count_per_row(self) -> [u32; 9] {
+        std::array::from_fn(|row| (self & Pattern::row_mask(row)).count())
+    }
+
+    /// Number of cells in each column.
+    pub fn cells_count_per_col(self) -> [u32; 9] {
+        std::array::from_fn(|col| (self & Pattern::col_mask(col)).count())
+    }
+
+    /// Number of cells in each box.
+    pub fn cells_count_per_box(self) -> [u32; 9] {
+        std::array::from_fn(|box_| (self & Pattern::box_mask(box_)).count())
+    }
+
+    /// All cells in the row.
+    pub fn row_mask(row: usize) -> Pattern {
+        UNIT_MASKS[0][row]
+    }
+
+    /// All cells in the column.
+    pub fn col_mask(col: usize) -> Pattern {
+        UNIT_MASKS[1][col]
+    }
+
+    /// All cells in the box.  Boxes are indexed row-major, like cells.
+    pub fn box_mask(box_: usize) -> Pattern {
+        UNIT_MASKS[2][box_]
+    }
+
+    /// All cells in the unit.
+    pub fn unit_mask(unit: Unit) -> Pattern {
+        match unit {
+            Unit::Row(row) => Pattern::row_mask(row),
+            Unit::Col(col) => Pattern::col_mask(col),
+            Unit::Box(box_) => Pattern::box_mask(box_),
+        }
+    }
+
+    /// Number of the pattern's cells in the unit.
+    pub fn count_set_in_unit(self, unit: Unit) -> u32 {
+        self.cells_set_in_unit(unit).count()
+    }
+
+    /// The pattern's cells in the unit.
+    pub fn cells_set_in_unit(self, unit: Unit) -> Pattern {
+        self & Pattern::unit_mask(unit)
+    }
+
+    /// Each row index with the pattern's cells in that row.
+    pub fn rows_iter(self) -> impl Iterator<Item = (usize, Pattern)> {
+        (0..9).map(move |row| (row, self & Pattern::row_mask(row)))
+    }
+
+    /// Each column index with the pattern's cells in that column.
+    pub fn cols_iter(self) -> impl Iterator<Item = (usize, Pattern)> {
+        (0..9).map(move |col| (col, self & Pattern::col_mask(col)))
+    }
+
+    /// Each box index with the pattern's cells in that box.
+    pub fn boxes_iter(self) -> impl Iterator<Item = (usize, Pattern)> {
+        (0..9).map(move |box_| (box_, self & Pattern::box_mask(box_)))
+    }
+
+    /// Cached pattern of the 20 other cells sharing a row, column, or box with
+    /// the cell.  Cells are numbered row-major, `9 * row + col`.
+    pub fn peers(cell_idx: usize) -> Pattern {
+        static PEERS: OnceLock<[Pattern; 81]> = OnceLock::new();
+
+        PEERS.get_or_init(|| {
+            let mut peers = [Pattern::EMPTY; 81];
+            for (idx, peer) in peers.iter_mut().enumerate() {
+                let (row, col) = (idx / 9, idx % 9);
+                *peer = Pattern::row_mask(row)
+                    | Pattern::col_mask(col)
+                    | Pattern::box_mask(row / 3 * 3 + col / 3);
+                peer.remove(row, col);
+            }
+            peers
+        })[cell_idx]
+    }
+
+    /// The pattern after moving every cell.
+    pub fn oriented(self, orientation: Orientation) -> Pattern {
+        let mut out = Pattern::EMPTY;
+        for row in 0..9 {
+            for col in 0..9 {
+                if self.has(row, col) {
+                    let (row, col) = orientation.apply(row, col);
+                    out = out.with(row, col);
+                }
+            }
+        }
+        out
+    }
+
+    /// Do all cells lie in one row?  True for the empty pattern.
+    pub fn is_single_row(self) -> bool {
+        (0..9).any(|row| self.is_subset(Pattern::row_mask(row)))
+    }
+
+    /// Do all cells lie in one column?  True for the empty pattern.
+    pub fn is_single_col(self) -> bool {
+        (0..9).any(|col| self.is_subset(Pattern::col_mask(col)))
+    }
+
+    /// Do all cells lie in one box?  True for the empty pattern.
+    pub fn is_single_box(self) -> bool {
+        (0..9).any(|box_| self.is_subset(Pattern::box_mask(box_)))
+    }
+
     pub fn is_subset(self, other: Pattern) -> bool {
         (self & other) == self
     }
@@ -41,8 +313,30 @@ impl Pattern {
     pub fn intersects(self, other: Pattern) -> bool {
         (self & other) != Pattern::EMPTY
     }
+
+    /// Cells in exactly one of the two patterns.  Same as `self ^ other`.
+    pub fn symmetric_difference(self, other: Pattern) -> Pattern {
+        self ^ other
+    }
 }
 
+/// Cells of every row, column, and box, in that order.
+const UNIT_MASKS: [[Pattern; 9]; 3] = {
+    let mut masks = [[Pattern::EMPTY; 9]; 3];
+    let mut unit = 0;
+    while unit < 9 {
+        let mut i = 0;
+        while i < 9 {
+            masks[0][unit] = masks[0][unit].with(unit, i);
+            masks[1][unit] = masks[1][unit].with(i, unit);
+            masks[2][unit] = masks[2][unit].with(unit / 3 * 3 + i / 3, unit % 3 * 3 + i % 3);
+            i += 1;
+        }
+        unit += 1;
+    }
+    masks
+};
+
 impl BitAnd for Pattern {
     type Output = Pattern;
     fn bitand(self, rhs: Self) -> Pattern {
@@ -65,6 +359,17 @@ impl BitOr for Pattern {
     }
 }
 
+impl BitXor for Pattern {
+    type Output = Pattern;
+    fn bitxor(self, rhs: Self) -> Pattern {
+        Pattern([
+            self.0[0] ^ rhs.0[0],
+            self.0[1] ^ rhs.0[1],
+            self.0[2] ^ rhs.0[2],
+        ])
+    }
+}
+
 impl Not for Pattern {
     type Output = Pattern;
     fn not(self) -> Self::Output {
@@ -76,7 +381,7 @@ impl std::fmt::Display for Pattern {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for row in 0..9 {
             if row == 3 || row == 6 {
-                write!(f, "---+---+---\n")?;
+                writeln!(f, "---+---+---")?;
             }
             for col in 0..9 {
                 if col == 3 || col == 6 {
@@ -88,8 +393,206 @@ impl std::fmt::Display for Pattern {
                     write!(f, " ")?;
                 }
             }
-            write!(f, "\n")?;
+            writeln!(f)?;
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unit_masks() {
+        for i in 0..9 {
+            for j in 0..9 {
+                assert!(Pattern::row_mask(i).has(i, j));
+                assert!(Pattern::col_mask(i).has(j, i));
+                assert!(Pattern::box_mask(i).has(i / 3 * 3 + j / 3, i % 3 * 3 + j % 3));
+            }
+            assert_eq!(Pattern::row_mask(i).count(), 9);
+            assert_eq!(Pattern::col_mask(i).count(), 9);
+            assert_eq!(Pattern::box_mask(i).count(), 9);
+        }
+    }
+
+    #[test]
+    fn single_unit_predicates() {
+        assert!(Pattern::row_mask(0).is_single_row());
+        assert!(!Pattern::row_mask(0).is_single_col());
+        assert!(Pattern::col_mask(4).is_single_col());
+        assert!(Pattern::box_mask(8).is_single_box());
+        assert!(Pattern::bit(2, 2).with(2, 0).is_single_box());
+
+        assert!(!Pattern::FULL.is_single_row());
+        assert!(!Pattern::FULL.is_single_col());
+        assert!(!Pattern::FULL.is_single_box());
+    }
+
+    #[test]
+    fn peers() {
+        for idx in 0..81 {
+            let peers = Pattern::peers(idx);
+            assert_eq!(peers.count(), 20);
+            assert!(!peers.has(idx / 9, idx % 9));
+        }
+        assert!(Pattern::peers(0).has(2, 2));
+        assert!(!Pattern::peers(40).has(3, 0));
+    }
+
+    /// Pattern with some cells in every row, column, and box.
+    fn scattered() -> Pattern {
+        Pattern::peers(10).with(8, 8).with(7, 3).with(5, 5)
+    }
+
+    #[test]
+    fn unit_iterators() {
+        for p in [Pattern::EMPTY, Pattern::FULL, scattered()] {
+            let rows: u32 = p.rows_iter().map(|(_, r)| r.count()).sum();
+            let cols: u32 = p.cols_iter().map(|(_, c)| c.count()).sum();
+            let boxes: u32 = p.boxes_iter().map(|(_, b)| b.count()).sum();
+            assert_eq!((rows, cols, boxes), (p.count(), p.count(), p.count()));
+        }
+        for (row, cells) in scattered().rows_iter() {
+            assert!(cells.is_subset(Pattern::row_mask(row)));
+        }
+    }
+
+    #[test]
+    fn hex_round_trip() {
+        assert_eq!(Pattern::FULL.fmt_hex(), "FFFFFFFFFFFFFFFF1FFFF");
+        assert_eq!(Pattern::EMPTY.fmt_hex(), "0".repeat(21));
+        for p in [Pattern::EMPTY, Pattern::FULL, scattered()] {
+            assert_eq!(Pattern::from_hex(&p.fmt_hex()), Ok(p));
+        }
+        assert_eq!(
+            Pattern::from_hex("FFFFFFFFFFFFFFFF2FFFF"),
+            Err(PatternParseError)
+        );
+        assert_eq!(Pattern::from_hex("FFFF"), Err(PatternParseError));
+    }
+
+    #[test]
+    fn compact_form() {
+        assert_eq!(Pattern::EMPTY.fmt_compact(), "0".repeat(81));
+        assert_eq!(Pattern::FULL.fmt_compact(), "1".repeat(81));
+
+        let p = scattered();
+        let compact = p.fmt_compact();
+        assert_eq!(compact.len(), 81);
+        for (i, c) in compact.chars().enumerate() {
+            assert_eq!(c == '1', p.has(i / 9, i % 9));
+        }
+    }
+
+    #[test]
+    fn row_bits() {
+        for row in 0..9 {
+            let mut expected = [0; 9];
+            expected[row] = 0x1FF;
+            assert_eq!(Pattern::row_mask(row).to_row_bits(), expected);
+        }
+        assert_eq!(Pattern::col_mask(3).to_row_bits(), [1 << 3; 9]);
+
+        for p in [Pattern::EMPTY, Pattern::FULL, scattered()] {
+            assert_eq!(Pattern::from_row_bits(&p.to_row_bits()), p);
+        }
+        assert_eq!(Pattern::from_row_bits(&[0xFFFF; 9]), Pattern::FULL);
+    }
+
+    #[test]
+    fn count_set_in_unit() {
+        assert_eq!(Pattern::FULL.count_set_in_unit(Unit::Row(3)), 9);
+        assert_eq!(Pattern::EMPTY.count_set_in_unit(Unit::Box(0)), 0);
+
+        let p = scattered();
+        assert_eq!(p.count_set_in_unit(Unit::Row(1)), 8);
+        assert_eq!(p.count_set_in_unit(Unit::Col(5)), 2);
+        for (box_, count) in p.cells_count_per_box().into_iter().enumerate() {
+            assert_eq!(p.count_set_in_unit(Unit::Box(box_)), count);
+        }
+    }
+
+    #[test]
+    fn cells_set_in_unit() {
+        for p in [Pattern::EMPTY, Pattern::FULL, scattered()] {
+            for i in 0..9 {
+                for unit in [Unit::Row(i), Unit::Col(i), Unit::Box(i)] {
+                    let cells = p.cells_set_in_unit(unit);
+                    assert!(cells.is_subset(Pattern::unit_mask(unit)));
+                    assert!(cells.is_subset(p));
+                }
+            }
+        }
+        assert_eq!(
+            Pattern::FULL.cells_set_in_unit(Unit::Col(2)),
+            Pattern::col_mask(2)
+        );
+        assert_eq!(
+            scattered().cells_set_in_unit(Unit::Row(8)),
+            Pattern::bit(8, 1).with(8, 8)
+        );
+    }
+
+    #[test]
+    fn line_notation() {
+        let corner = Pattern::bit(0, 0);
+        assert_eq!(corner.to_sudoku_line_notation(), "r1c1");
+        assert_eq!(Pattern::from_sudoku_line_notation("r1c1"), Ok(corner));
+        assert_eq!(Pattern::EMPTY.to_sudoku_line_notation(), "");
+        assert_eq!(
+            Pattern::bit(1, 3).with(8, 8).to_sudoku_line_notation(),
+            "r2c4 r9c9"
+        );
+
+        for p in [Pattern::EMPTY, Pattern::FULL, scattered()] {
+            assert_eq!(
+                Pattern::from_sudoku_line_notation(&p.to_sudoku_line_notation()),
+                Ok(p)
+            );
+        }
+        assert_eq!(
+            Pattern::from_sudoku_line_notation("  r9c9\tr2c4 "),
+            Ok(Pattern::bit(1, 3).with(8, 8))
+        );
+        for bad in ["r0c1", "r1c10", "c1r1", "r1", "r1c1,r2c2"] {
+            assert_eq!(
+                Pattern::from_sudoku_line_notation(bad),
+                Err(PatternParseError)
+            );
+        }
+    }
+
+    #[test]
+    fn symmetric_difference() {
+        let p = scattered();
+        assert_eq!(p.symmetric_difference(p), Pattern::EMPTY);
+        assert_eq!(Pattern::EMPTY.symmetric_difference(p), p);
+        assert_eq!(p.symmetric_difference(Pattern::FULL), !p);
+
+        let q = Pattern::row_mask(1);
+        assert_eq!(p.symmetric_difference(q), p ^ q);
+        assert_eq!(p ^ q, (p | q) & !(p & q));
+        assert_eq!(p ^ q, q ^ p);
+    }
+
+    #[test]
+    fn cells_count_per_unit_sums_to_count() {
+        let mut rng = crate::test_rng(5);
+        let patterns = (0..50).map(|_| crate::test_pattern(&mut rng, 50));
+        for p in patterns.chain([Pattern::EMPTY, Pattern::FULL, scattered()]) {
+            let rows = p.cells_count_per_row();
+            let cols = p.cells_count_per_col();
+            let boxes = p.cells_count_per_box();
+            for counts in [rows, cols, boxes] {
+                assert_eq!(counts.iter().sum::<u32>(), p.count(), "{}", p);
+            }
+            for i in 0..9 {
+                assert_eq!(rows[i], p.count_set_in_unit(Unit::Row(i)));
+                assert_eq!(cols[i], p.count_set_in_unit(Unit::Col(i)));
+                assert_eq!(boxes[i], p.count_set_in_unit(Unit::Box(i)));
+            }
+        }
+    }
+}
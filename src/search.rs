@@ -0,0 +1,171 @@
+use crate::{pattern::Pattern, Possibilities, Solution, Template};
+
+/// Exhaustive search by digit, the second phase of solving.
+///
+/// Each digit has a list of possible templates.  The search picks one
+/// template per digit, in the order given, and keeps every combination that
+/// doesn't overlap.
+#[derive(Clone, Debug)]
+pub struct SearchTree {
+    templates: [(usize, Vec<Template>); 9],
+    max_solutions: usize,
+}
+
+impl SearchTree {
+    /// Search over the templates possible in `possibilities`, digits from
+    /// most- to least-restricted.  See [`Template::sorted_by_restriction`].
+    pub fn new(possibilities: &Possibilities) -> SearchTree {
+        SearchTree::from_templates(Template::sorted_by_restriction(possibilities.patterns()))
+    }
+
+    /// Search over the given `(digit, templates)` pairs (0-indexed digits),
+    /// in the order given.
+    pub fn from_templates(templates: [(usize, Vec<Template>); 9]) -> SearchTree {
+        SearchTree {
+            templates,
+            max_solutions: usize::MAX,
+        }
+    }
+
+    /// Stop after this many solutions.  Unlimited by default.
+    pub fn max_solutions(mut self, max_solutions: usize) -> SearchTree {
+        self.max_solutions = max_solutions;
+        self
+    }
+
+    /// Every solution, up to the maximum.
+    pub fn run(self) -> Vec<Solution> {
+        self.run_with(None)
+    }
+
+    /// Like [`run`](SearchTree::run), reporting the fraction of the first
+    /// digit's templates explored so far.
+    pub(crate) fn run_with_progress(self, progress: &mut dyn FnMut(f64)) -> Vec<Solution> {
+        self.run_with(Some(progress))
+    }
+
+    fn run_with(self, progress: Option<&mut dyn FnMut(f64)>) -> Vec<Solution> {
+        let mut solutions = Vec::new();
+        if self.max_solutions == 0 {
+            return solutions;
+        }
+        self.walk(progress, &mut |solution| {
+            solutions.push(solution.clone());
+            solutions.len() < self.max_solutions
+        });
+        solutions
+    }
+
+    /// Hand each solution to `emit`, without copying it.  Stops as soon as
+    /// `emit` returns `false`; the maximum is ignored.
+    pub(crate) fn for_each(&self, emit: &mut dyn FnMut(&Solution) -> bool) {
+        self.walk(None, emit);
+    }
+
+    /// Number of solutions, counting no further than `limit`.
+    ///
+    /// The last digit isn't searched.  Once the others are placed, the cells
+    /// left over always form a layout, so a branch is a solution as soon as
+    /// they fit the last digit.  That only holds if the last digit's
+    /// templates are all of those within some pattern, as from
+    /// [`Template::within`].
+    pub(crate) fn count_up_to(&self, limit: usize) -> usize {
+        let Some(((_digit, last), rest)) = self.templates.split_last() else {
+            return 0;
+        };
+        let last_cells = last
+            .iter()
+            .fold(Pattern::EMPTY, |acc, t| acc | t.as_pattern());
+
+        let mut count = 0;
+        if limit > 0 {
+            let mut solution = Solution::default();
+            let mut emit = |_: &Solution, filled: Pattern| {
+                if (!filled).is_subset(last_cells) {
+                    count += 1;
+                }
+                count < limit
+            };
+            SearchTree::step(&mut emit, &mut solution, Pattern::EMPTY, rest, None);
+        }
+        count
+    }
+
+    fn walk(&self, progress: Option<&mut dyn FnMut(f64)>, emit: &mut dyn FnMut(&Solution) -> bool) {
+        // web_sys::console::time_with_label("solution search");
+        let mut solution = Solution::default();
+        SearchTree::step(
+            &mut |solution, _filled| emit(solution),
+            &mut solution,
+            Pattern::EMPTY,
+            &self.templates,
+            progress,
+        );
+        // web_sys::console::time_end_with_label("solution search");
+    }
+
+    /// Try each template for the first remaining digit.  Returns whether to
+    /// keep going.
+    fn step(
+        emit: &mut dyn FnMut(&Solution, Pattern) -> bool,
+        solution: &mut Solution,
+        filled: Pattern,
+        templates: &[(usize, Vec<Template>)],
+        mut progress: Option<&mut dyn FnMut(f64)>,
+    ) -> bool {
+        match templates.split_first() {
+            None => emit(solution, filled),
+
+            Some(((digit, possible), rest)) => {
+                for (i, &template) in possible.iter().enumerate() {
+                    if template.as_pattern().intersects(filled) {
+                        continue;
+                    }
+
+                    solution.0[*digit] = template;
+
+                    let filled = filled | template.as_pattern();
+                    let keep_going = SearchTree::step(emit, solution, filled, rest, None);
+
+                    // only reported by the outermost digit
+                    if let Some(progress) = &mut progress {
+                        progress((i + 1) as f64 / possible.len() as f64);
+                    }
+
+                    if !keep_going {
+                        return false;
+                    }
+                }
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn max_solutions_is_respected() {
+        let empty = Possibilities::new();
+        for max in [0, 1, 5] {
+            let solutions = SearchTree::new(&empty).max_solutions(max).run();
+            assert_eq!(solutions.len(), max);
+        }
+    }
+
+    #[test]
+    fn count_up_to_matches_run() {
+        let puzzle = Possibilities::try_from(
+            "...................98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79",
+        )
+        .unwrap();
+        let tree = SearchTree::new(&puzzle);
+        let all = tree.clone().run().len();
+        assert!(all > 1);
+        for limit in [0, 1, 2, all, all + 1] {
+            assert_eq!(tree.count_up_to(limit), all.min(limit));
+        }
+    }
+}
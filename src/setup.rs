@@ -1,4 +1,9 @@
-use crate::{pattern::Pattern, Solution, Template};
+use crate::{
+    format::{clue_cells, exchange_format},
+    parse::{parse, parse_exchange, ParseError},
+    pattern::{Pattern, Unit},
+    SearchTree, Solution, Template,
+};
 
 /**
  Prepared form of a puzzle, applying logic to the input.
@@ -52,24 +57,151 @@ use crate::{pattern::Pattern, Solution, Template};
  more work is enqueued.
 */
 
-#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Debug)]
 pub struct Possibilities {
     /// [`Pattern`] for each digit
-    pub patterns: [Pattern; 9],
+    patterns: [Pattern; 9],
 
     /// Queue of `(row, col, digit)` triples to eliminate
     work_queue: Vec<(u8, u8, u8)>,
     cell_constraints: [[u8; 9]; 9], // [row][col] -> number of digits in cell
     row_constraints: [[u8; 9]; 9],  // [row][dig] -> number of `dig`s in row
     col_constraints: [[u8; 9]; 9],  // [col][dig] -> number of `dig`s in col
-    box_constraints: [[u8; 9]; 9],  // [box][dig] -> number of `dig`s in box
-                                    // boxes are indexed row-major, like `Pattern` cells
+    box_constraints: [[u8; 9]; 9],  // [box][dig] -> number of `dig`s in box, row-major like cells
+
+    /// Cells given to [`Possibilities::set`], as `(row, col, digit)`
+    clues: Vec<(usize, usize, u8)>,
+
+    /// Events seen during propagation, if recording
+    events: Option<Vec<PropagationEvent>>,
+
+    /// Eliminated `(row, col, digit)` triples, while a checkpoint is open
+    journal: Option<Vec<(u8, u8, u8)>>,
+    /// Checkpoints not yet rolled back or released
+    open_checkpoints: usize,
+
+    /// Eliminations and their reasons, if recording
+    eliminations: Option<EliminationLog>,
+}
+
+/// Something notable that happened while applying logic.
+///
+/// See [`Possibilities::record_events`].
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum PropagationEvent {
+    /// Every digit in the unit has been found.
+    UnitCompleted(Unit),
+}
+
+/// Logic which removed a candidate.  See [`CandidateElimination`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Technique {
+    /// Another digit was given in the cell.
+    Given,
+    /// The digit was found in a cell in the same row, column, or box.
+    NakedSingle,
+    /// Another digit has only one place left in its row, column, or box.
+    HiddenSingle,
+    /// The digit's cells in a box lie in one row or column.
+    PointingPair,
+    /// The digit's cells in a row or column lie in one box.
+    BoxLineReduction,
+    /// The digit's cells in some rows lie in as many columns, or vice versa.
+    Fish,
+    /// Placing the digit in either of its two cells in a unit rules the
+    /// candidate out.
+    ForcingChain,
+    /// The candidate was missing from another state.  See
+    /// [`Possibilities::intersect`].
+    Merged,
+}
+
+/// A candidate removed by logic, and why.
+///
+/// See [`Possibilities::record_eliminations`].
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct CandidateElimination {
+    pub row: usize,
+    pub col: usize,
+    /// 1 through 9
+    pub digit: u8,
+    pub technique: Technique,
+    /// Cells which forced the elimination, as `(row, col)`
+    pub trigger_cells: Vec<(usize, usize)>,
+}
+
+/// Reasons for queued eliminations, while recording.
+#[derive(Clone, Debug, Default)]
+struct EliminationLog {
+    /// Reasons given since the work queue was last empty
+    reasons: Vec<(Technique, Vec<(usize, usize)>)>,
+    /// Index into `reasons` for each entry of the work queue
+    queued: Vec<Option<usize>>,
+    entries: Vec<CandidateElimination>,
+}
+
+/// Outcome of [`Possibilities::step`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum StepResult {
+    /// No logic was left to apply.
+    Stable,
+    /// The cell `(row, col)` was narrowed down to the digit (1 through 9).
+    Placed(usize, usize, u8),
+    /// The puzzle has no solution.
+    Contradiction,
 }
 
 /// Error returned when initializing a [`Possibilities`] fails.
 #[derive(Clone, Copy, Debug)]
 pub struct ImpossiblePuzzle;
 
+impl std::fmt::Display for ImpossiblePuzzle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "puzzle has no solution")
+    }
+}
+
+impl std::error::Error for ImpossiblePuzzle {}
+
+/// Copy of the constraint counts inside a [`Possibilities`], for debugging.
+///
+/// Each count is the number of places a digit could still go.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct ConstraintCounts {
+    /// `[row][col]` -> number of digits in cell
+    pub cell: [[u8; 9]; 9],
+    /// `[row][digit]` -> number of `digit`s in row
+    pub row: [[u8; 9]; 9],
+    /// `[col][digit]` -> number of `digit`s in column
+    pub col: [[u8; 9]; 9],
+    /// `[box][digit]` -> number of `digit`s in box
+    pub box_: [[u8; 9]; 9],
+}
+
+/// Saved state of a [`Possibilities`], for backtracking.
+///
+/// See [`Possibilities::snapshot`] and [`Possibilities::restore`].
+#[derive(Clone, Debug)]
+pub struct PossibilitiesSnapshot(Possibilities);
+
+/// Token for undoing changes to a [`Possibilities`].
+///
+/// See [`Possibilities::checkpoint`] and [`Possibilities::rollback`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Checkpoint {
+    journal_len: usize,
+    clues_len: usize,
+    /// Checkpoints already open when this one was taken
+    depth: usize,
+    /// Work queued but not yet applied, which a later `set` would apply
+    work_queue: Vec<(u8, u8, u8)>,
+    /// Reasons for the queued work, if recording eliminations
+    reasons: Option<QueuedReasons>,
+}
+
+/// [`EliminationLog::reasons`] and [`EliminationLog::queued`].
+type QueuedReasons = (Vec<(Technique, Vec<(usize, usize)>)>, Vec<Option<usize>>);
+
 impl Possibilities {
     /// Fresh logic machine where every digit is possible in every cell.
     pub fn new() -> Possibilities {
@@ -81,35 +213,742 @@ impl Possibilities {
             row_constraints: [[9; 9]; 9],
             col_constraints: [[9; 9]; 9],
             box_constraints: [[9; 9]; 9],
+
+            clues: Vec::new(),
+            events: None,
+            journal: None,
+            open_checkpoints: 0,
+            eliminations: None,
         }
     }
 
+    /// Cells given to [`set`](Possibilities::set), as `(row, col, digit)`.
+    ///
+    /// Unlike [`patterns`](Possibilities::patterns), these don't include
+    /// digits found by logic.
+    pub fn clues(&self) -> Vec<(usize, usize, u8)> {
+        self.clues.clone()
+    }
+
+    /// Sudoku Exchange form of the clues:  81 digits in row-major order, `0`
+    /// for an empty cell.  Digits found by logic are left out.
+    pub fn to_sudoku_exchange_format(&self) -> String {
+        exchange_format(&clue_cells(self))
+    }
+
+    /// Read a puzzle from a Sudoku Exchange line, as written by
+    /// [`to_sudoku_exchange_format`](Possibilities::to_sudoku_exchange_format).
+    ///
+    /// Fields besides the grid, like a hash or rating, are ignored.
+    pub fn from_sudoku_exchange_format(s: &str) -> Result<Possibilities, ParseError> {
+        Ok(Possibilities::try_from(parse_exchange(s)?)?)
+    }
+
+    /// Start recording [`PropagationEvent`]s.
+    pub fn record_events(&mut self) {
+        self.events.get_or_insert_with(Vec::new);
+    }
+
+    /// Events recorded since the last call.  Empty if not recording.
+    pub fn take_events(&mut self) -> Vec<PropagationEvent> {
+        self.events.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Start recording every [`CandidateElimination`].
+    pub fn record_eliminations(&mut self) {
+        self.eliminations.get_or_insert_with(|| EliminationLog {
+            // anything already queued has no reason
+            queued: vec![None; self.work_queue.len()],
+            ..Default::default()
+        });
+    }
+
+    /// Eliminations recorded since the last call.  Empty if not recording.
+    pub fn take_eliminations(&mut self) -> Vec<CandidateElimination> {
+        self.eliminations
+            .as_mut()
+            .map(|log| std::mem::take(&mut log.entries))
+            .unwrap_or_default()
+    }
+
+    /// Keep only the candidates also possible in `other`, and apply logic.
+    ///
+    /// Useful for combining deductions made separately from the same puzzle.
+    pub fn intersect(&mut self, other: &Possibilities) -> Result<(), ImpossiblePuzzle> {
+        self.note_reason(Technique::Merged, Pattern::EMPTY);
+        for digit in 0..9 {
+            let missing = self.patterns[digit] & !other.patterns[digit];
+            missing.cells().for_each(|cell| self.enqueue(cell, digit));
+        }
+        self.work()
+    }
+
+    /// Possibilities where each digit can go anywhere covered by its
+    /// templates.  Logic is not applied.
+    ///
+    /// This is the reverse of [`as_template_sets`](Possibilities::as_template_sets)
+    /// only up to the templates:  a candidate that no template covers is
+    /// dropped, so the round trip can tighten the patterns.  The template
+    /// sets themselves survive unchanged.
+    ///
+    /// `None` if some digit has no templates, or some cell, row, column, or
+    /// box is left without a place for every digit.
+    pub fn from_template_sets(templates: &[Vec<Template>; 9]) -> Option<Possibilities> {
+        let mut puzzle = Possibilities::new();
+        for (pattern, templates) in puzzle.patterns.iter_mut().zip(templates) {
+            *pattern = templates
+                .iter()
+                .fold(Pattern::EMPTY, |acc, t| acc | t.as_pattern());
+        }
+        puzzle.recount().ok()?;
+        Some(puzzle)
+    }
+
+    /// Possible templates for each digit.
+    pub fn as_template_sets(&self) -> [Vec<Template>; 9] {
+        self.patterns.map(|p| Template::within(p).collect())
+    }
+
+    /// The cell's digit (1 through 9), if only one is possible.
+    pub fn get(&self, row: usize, col: usize) -> Option<u8> {
+        match self.cell_constraints[row][col] {
+            1 => Some(self.find_in_cell(row, col) as u8 + 1),
+            _ => None,
+        }
+    }
+
+    /// [`Pattern`] of cells which can still hold each digit, 0-indexed.
+    pub fn patterns(&self) -> &[Pattern; 9] {
+        &self.patterns
+    }
+
+    /// [`Pattern`] of cells which can still hold `digit` (1 through 9).
+    pub fn patterns_for_digit(&self, digit: u8) -> Pattern {
+        assert!(
+            (1..=9).contains(&digit),
+            "digit {} out of range 1..=9",
+            digit
+        );
+        self.patterns[digit as usize - 1]
+    }
+
+    /// Mutable [`Pattern`] of cells which can still hold `digit` (1 through 9).
+    ///
+    /// Changes are not propagated.
+    pub fn patterns_for_digit_mut(&mut self, digit: u8) -> &mut Pattern {
+        assert!(
+            (1..=9).contains(&digit),
+            "digit {} out of range 1..=9",
+            digit
+        );
+        &mut self.patterns[digit as usize - 1]
+    }
+
+    /// Cells which can still hold at least one digit.
+    ///
+    /// Always [`Pattern::FULL`] unless the puzzle is impossible.
+    pub fn union_patterns(&self) -> Pattern {
+        self.patterns.iter().fold(Pattern::EMPTY, |acc, &p| acc | p)
+    }
+
+    /// Cells which can still hold every digit.
+    pub fn intersection_patterns(&self) -> Pattern {
+        self.patterns.iter().fold(Pattern::FULL, |acc, &p| acc & p)
+    }
+
     /// Remove all other digits from this cell, and apply logic.
-    pub fn set(&mut self, row: u8, col: u8, digit: u8) -> Result<(), ImpossiblePuzzle> {
-        self.enqueue_others((row as usize, col as usize), digit as usize - 1);
+    pub fn set(&mut self, row: usize, col: usize, digit: u8) -> Result<(), ImpossiblePuzzle> {
+        self.clues.push((row, col, digit));
+        self.enqueue_others((row, col), digit as usize - 1, Technique::Given);
+        self.work()
+    }
+
+    /// Like [`set`](Possibilities::set), but leave the logic to be applied
+    /// by [`step`](Possibilities::step).
+    ///
+    /// Any method that applies logic, like `set`, also applies what is left.
+    pub fn set_deferred(&mut self, row: usize, col: usize, digit: u8) {
+        self.clues.push((row, col, digit));
+        self.enqueue_others((row, col), digit as usize - 1, Technique::Given);
+    }
+
+    /// Remove the digit of every single-digit cell from the rest of its row,
+    /// column, and box.  Returns whether anything was eliminated.
+    ///
+    /// [`set`](Possibilities::set) already does this as it goes, so this only
+    /// makes progress on a state that hasn't been fully propagated.
+    pub fn apply_naked_singles(&mut self) -> Result<bool, ImpossiblePuzzle> {
+        let before = self.patterns;
+        for row in 0..9 {
+            for col in 0..9 {
+                if self.cell_constraints[row][col] == 1 {
+                    self.enqueue_adjacent((row, col), self.find_in_cell(row, col));
+                }
+            }
+        }
+        self.work()?;
+        Ok(self.patterns != before)
+    }
+
+    /// Remove all other digits from every cell holding the only place for a
+    /// digit in its row, column, or box.  Returns whether anything was
+    /// eliminated.
+    ///
+    /// [`set`](Possibilities::set) already does this as it goes, so this only
+    /// makes progress on a state that hasn't been fully propagated.
+    pub fn apply_hidden_singles(&mut self) -> Result<bool, ImpossiblePuzzle> {
+        let before = self.patterns;
+        for unit in 0..9 {
+            for digit in 0..9 {
+                if self.row_constraints[unit][digit] == 1 {
+                    let cell = (unit, self.find_in_row(unit, digit));
+                    self.enqueue_others(cell, digit, Technique::HiddenSingle);
+                }
+                if self.col_constraints[unit][digit] == 1 {
+                    let cell = (self.find_in_col(unit, digit), unit);
+                    self.enqueue_others(cell, digit, Technique::HiddenSingle);
+                }
+                if self.box_constraints[unit][digit] == 1 {
+                    let (row, col) = (unit / 3 * 3, unit % 3 * 3);
+                    let cell = self.find_in_box(row, col, digit);
+                    self.enqueue_others(cell, digit, Technique::HiddenSingle);
+                }
+            }
+        }
+        self.work()?;
+        Ok(self.patterns != before)
+    }
+
+    /// Like [`apply_hidden_singles`](Possibilities::apply_hidden_singles), but
+    /// skips cells that are already down to one digit.  Every row, column,
+    /// and box is scanned before any logic is applied.
+    ///
+    /// On a state that's mostly solved, this enqueues far less work.
+    ///
+    /// The constraint counts are rebuilt first, so this also finds hidden
+    /// singles left by changes through
+    /// [`patterns_for_digit_mut`](Possibilities::patterns_for_digit_mut).
+    pub fn apply_hidden_single_in_all_units(&mut self) -> Result<bool, ImpossiblePuzzle> {
+        self.recount()?;
+
+        let mut found = Vec::new();
+        for unit in 0..9 {
+            for digit in 0..9 {
+                if self.row_constraints[unit][digit] == 1 {
+                    found.push(((unit, self.find_in_row(unit, digit)), digit));
+                }
+                if self.col_constraints[unit][digit] == 1 {
+                    found.push(((self.find_in_col(unit, digit), unit), digit));
+                }
+                if self.box_constraints[unit][digit] == 1 {
+                    let (row, col) = (unit / 3 * 3, unit % 3 * 3);
+                    found.push((self.find_in_box(row, col, digit), digit));
+                }
+            }
+        }
+
+        let before = self.patterns;
+        for ((row, col), digit) in found {
+            if self.cell_constraints[row][col] > 1 {
+                self.enqueue_others((row, col), digit, Technique::HiddenSingle);
+            }
+        }
+        self.work()?;
+        Ok(self.patterns != before)
+    }
+
+    /// Current constraint counts.
+    pub fn constraint_counts(&self) -> ConstraintCounts {
+        ConstraintCounts {
+            cell: self.cell_constraints,
+            row: self.row_constraints,
+            col: self.col_constraints,
+            box_: self.box_constraints,
+        }
+    }
+
+    /// Unit and digit (1 through 9) with the fewest places left, not counting
+    /// digits already found.  Ties go to rows, then columns, then boxes.
+    ///
+    /// `None` if every digit has been found everywhere.
+    pub fn most_constrained_unit(&self) -> Option<(Unit, u8)> {
+        let units = [
+            (&self.row_constraints, Unit::Row as fn(usize) -> Unit),
+            (&self.col_constraints, Unit::Col),
+            (&self.box_constraints, Unit::Box),
+        ];
+        units
+            .into_iter()
+            .flat_map(|(counts, unit)| {
+                (0..81).map(move |i| (counts[i / 9][i % 9], unit(i / 9), i % 9))
+            })
+            .filter(|&(count, _, _)| count > 1)
+            .min_by_key(|&(count, _, _)| count)
+            .map(|(_, unit, digit)| (unit, digit as u8 + 1))
+    }
+
+    /// If `digit` (1 through 9) has only one place in the box, remove all other
+    /// digits from that cell.  Returns whether anything was eliminated.
+    pub fn apply_hidden_single_in_box(
+        &mut self,
+        box_idx: usize,
+        digit: u8,
+    ) -> Result<bool, ImpossiblePuzzle> {
+        self.apply_hidden_single_in(Unit::Box(box_idx), digit)
+    }
+
+    /// If `digit` (1 through 9) has only one place in the row, remove all other
+    /// digits from that cell.  Returns whether anything was eliminated.
+    pub fn apply_hidden_single_in_row(
+        &mut self,
+        row: usize,
+        digit: u8,
+    ) -> Result<bool, ImpossiblePuzzle> {
+        self.apply_hidden_single_in(Unit::Row(row), digit)
+    }
+
+    /// If `digit` (1 through 9) has only one place in the column, remove all
+    /// other digits from that cell.  Returns whether anything was eliminated.
+    pub fn apply_hidden_single_in_col(
+        &mut self,
+        col: usize,
+        digit: u8,
+    ) -> Result<bool, ImpossiblePuzzle> {
+        self.apply_hidden_single_in(Unit::Col(col), digit)
+    }
+
+    fn apply_hidden_single_in(&mut self, unit: Unit, digit: u8) -> Result<bool, ImpossiblePuzzle> {
+        let cells = self.patterns_for_digit(digit) & Pattern::unit_mask(unit);
+        if cells.count() != 1 {
+            return Ok(false);
+        }
+
+        let before = self.patterns;
+        let cell = cells.cells().next().unwrap();
+        self.enqueue_others(cell, digit as usize - 1, Technique::HiddenSingle);
+        self.work()?;
+        Ok(self.patterns != before)
+    }
+
+    /// If a digit's cells in a box all lie in one row or column, remove the
+    /// digit from the rest of that row or column.  Returns whether anything
+    /// was eliminated.
+    pub fn apply_pointing_pairs(&mut self) -> Result<bool, ImpossiblePuzzle> {
+        let before = self.patterns;
+        for digit in 0..9 {
+            for box_ in 0..9 {
+                let box_mask = Pattern::box_mask(box_);
+                let cells = self.patterns[digit] & box_mask;
+                if cells == Pattern::EMPTY {
+                    continue;
+                }
+
+                for i in 0..3 {
+                    for line in [
+                        Pattern::row_mask(box_ / 3 * 3 + i),
+                        Pattern::col_mask(box_ % 3 * 3 + i),
+                    ] {
+                        let rest = self.patterns[digit] & line & !box_mask;
+                        if cells.is_subset(line) && rest != Pattern::EMPTY {
+                            self.note_reason(Technique::PointingPair, cells);
+                            rest.cells().for_each(|cell| self.enqueue(cell, digit));
+                        }
+                    }
+                }
+            }
+        }
+        self.work()?;
+        Ok(self.patterns != before)
+    }
+
+    /// If a digit's cells in a row or column all lie in one box, remove the
+    /// digit from the rest of that box.  Returns whether anything was
+    /// eliminated.
+    pub fn apply_box_line_reduction(&mut self) -> Result<bool, ImpossiblePuzzle> {
+        let before = self.patterns;
+        for digit in 0..9 {
+            for i in 0..9 {
+                for line in [Pattern::row_mask(i), Pattern::col_mask(i)] {
+                    let cells = self.patterns[digit] & line;
+                    if cells == Pattern::EMPTY {
+                        continue;
+                    }
+
+                    for box_ in 0..9 {
+                        let box_mask = Pattern::box_mask(box_);
+                        let rest = self.patterns[digit] & box_mask & !line;
+                        if cells.is_subset(box_mask) && rest != Pattern::EMPTY {
+                            self.note_reason(Technique::BoxLineReduction, cells);
+                            rest.cells().for_each(|cell| self.enqueue(cell, digit));
+                        }
+                    }
+                }
+            }
+        }
+        self.work()?;
+        Ok(self.patterns != before)
+    }
+
+    /// Apply both directions of intersection removal: pointing pairs and
+    /// box-line reduction.  Returns whether anything was eliminated.
+    pub fn apply_intersection_removal(&mut self) -> Result<bool, ImpossiblePuzzle> {
+        let pointing = self.apply_pointing_pairs()?;
+        let box_line = self.apply_box_line_reduction()?;
+        Ok(pointing || box_line)
+    }
+
+    /// Same as [`apply_intersection_removal`](Possibilities::apply_intersection_removal),
+    /// under its other common name.
+    pub fn apply_locked_candidates(&mut self) -> Result<bool, ImpossiblePuzzle> {
+        self.apply_intersection_removal()
+    }
+
+    /// For each row, column, or box with only two places for `digit` (1
+    /// through 9), try the digit in each place and apply logic.  Whatever
+    /// both tries rule out is eliminated; if one try fails, the digit goes in
+    /// the other place.  Returns whether anything was eliminated.
+    pub fn apply_digit_forcing_chain(&mut self, digit: u8) -> Result<bool, ImpossiblePuzzle> {
+        let before = self.patterns;
+        let units = (0..9).flat_map(|i| [Unit::Row(i), Unit::Col(i), Unit::Box(i)]);
+        for unit in units {
+            let cells = self.patterns_for_digit(digit) & Pattern::unit_mask(unit);
+            if cells.count() != 2 {
+                continue;
+            }
+
+            let tries: Vec<Option<Possibilities>> = cells
+                .cells()
+                .map(|(row, col)| {
+                    let mut branch = self.branch();
+                    branch.set(row, col, digit).ok().map(|_| branch)
+                })
+                .collect();
+
+            // Candidates left in either try.
+            let mut survivors = [Pattern::EMPTY; 9];
+            for branch in tries.iter().flatten() {
+                for (survivor, &pattern) in survivors.iter_mut().zip(&branch.patterns) {
+                    *survivor = *survivor | pattern;
+                }
+            }
+
+            self.note_reason(Technique::ForcingChain, cells);
+            for (d, &survivor) in survivors.iter().enumerate() {
+                let ruled_out = self.patterns[d] & !survivor;
+                ruled_out.cells().for_each(|cell| self.enqueue(cell, d));
+            }
+            self.work()?;
+        }
+        Ok(self.patterns != before)
+    }
+
+    /// Copy for trying out a guess, without any recording or history.
+    fn branch(&self) -> Possibilities {
+        Possibilities {
+            patterns: self.patterns,
+            work_queue: Vec::new(),
+            cell_constraints: self.cell_constraints,
+            row_constraints: self.row_constraints,
+            col_constraints: self.col_constraints,
+            box_constraints: self.box_constraints,
+            clues: Vec::new(),
+            events: None,
+            journal: None,
+            open_checkpoints: 0,
+            eliminations: None,
+        }
+    }
+
+    /// If a digit's cells in `base_size` rows all lie in `base_size` columns,
+    /// remove the digit from the rest of those columns; likewise with rows
+    /// and columns swapped.  Returns whether anything was eliminated.
+    ///
+    /// Sizes 2, 3, and 4 are the X-wing, swordfish, and jellyfish.
+    pub fn apply_naked_fish_general(&mut self, base_size: usize) -> Result<bool, ImpossiblePuzzle> {
+        assert!(
+            (2..=4).contains(&base_size),
+            "fish size {} out of range 2..=4",
+            base_size
+        );
+
+        let before = self.patterns;
+        for digit in 0..9 {
+            for base_is_row in [true, false] {
+                let unit = |is_row, i| match is_row {
+                    true => Pattern::row_mask(i),
+                    false => Pattern::col_mask(i),
+                };
+                let base = |i| unit(base_is_row, i);
+                let cover = |j| unit(!base_is_row, j);
+
+                // [base line] -> bit mask of cover lines holding the digit
+                let mut lines = [0u16; 9];
+                for (i, line) in lines.iter_mut().enumerate() {
+                    for j in 0..9 {
+                        if (self.patterns[digit] & base(i) & cover(j)) != Pattern::EMPTY {
+                            *line |= 1 << j;
+                        }
+                    }
+                }
+
+                for subset in 0u16..1 << 9 {
+                    if subset.count_ones() as usize != base_size {
+                        continue;
+                    }
+                    let chosen = (0..9).filter(|&i| subset & (1 << i) != 0);
+                    if chosen.clone().any(|i| lines[i] == 0) {
+                        continue;
+                    }
+
+                    let covered = chosen.clone().fold(0, |acc, i| acc | lines[i]);
+                    if covered.count_ones() as usize != base_size {
+                        continue;
+                    }
+
+                    let base_mask = chosen.fold(Pattern::EMPTY, |acc, i| acc | base(i));
+                    let cover_mask = (0..9)
+                        .filter(|&j| covered & (1 << j) != 0)
+                        .fold(Pattern::EMPTY, |acc, j| acc | cover(j));
+                    let rest = self.patterns[digit] & cover_mask & !base_mask;
+                    if rest != Pattern::EMPTY {
+                        let cells = self.patterns[digit] & base_mask;
+                        self.note_reason(Technique::Fish, cells);
+                        rest.cells().for_each(|cell| self.enqueue(cell, digit));
+                    }
+                }
+            }
+        }
+        self.work()?;
+        Ok(self.patterns != before)
+    }
+
+    /// Recount constraints from the patterns, then apply naked and hidden
+    /// singles everywhere.  Returns whether anything was eliminated.
+    ///
+    /// Use this after changing patterns directly with
+    /// [`patterns_for_digit_mut`](Possibilities::patterns_for_digit_mut).
+    pub fn apply_constraint_propagation_round(&mut self) -> Result<bool, ImpossiblePuzzle> {
+        self.recount()?;
+        let naked = self.apply_naked_singles()?;
+        let hidden = self.apply_hidden_singles()?;
+        Ok(naked || hidden)
+    }
+
+    /// Rebuild all constraint counts from the patterns.
+    fn recount(&mut self) -> Result<(), ImpossiblePuzzle> {
+        self.cell_constraints = [[0; 9]; 9];
+        self.row_constraints = [[0; 9]; 9];
+        self.col_constraints = [[0; 9]; 9];
+        self.box_constraints = [[0; 9]; 9];
+
+        for digit in 0..9 {
+            for row in 0..9 {
+                for col in 0..9 {
+                    if self.patterns[digit].has(row, col) {
+                        self.cell_constraints[row][col] += 1;
+                        self.row_constraints[row][digit] += 1;
+                        self.col_constraints[col][digit] += 1;
+                        self.box_constraints[row / 3 * 3 + col / 3][digit] += 1;
+                    }
+                }
+            }
+        }
+
+        let counts = [
+            self.cell_constraints,
+            self.row_constraints,
+            self.col_constraints,
+            self.box_constraints,
+        ];
+        if counts.iter().flatten().any(|unit| unit.contains(&0)) {
+            return Err(ImpossiblePuzzle);
+        }
+        Ok(())
+    }
+
+    /// Panic if the constraint counts don't match the patterns.
+    ///
+    /// For testing changes to the logic.  After a failed
+    /// [`set`](Possibilities::set) the counts are expected to be off.
+    #[cfg(test)]
+    fn check_invariants(&self) {
+        let mut fresh = self.clone();
+        let _ = fresh.recount();
+        assert_eq!(
+            self.constraint_counts(),
+            fresh.constraint_counts(),
+            "constraint counts don't match patterns"
+        );
+    }
+
+    /// Save the current state.
+    pub fn snapshot(&self) -> PossibilitiesSnapshot {
+        PossibilitiesSnapshot(self.clone())
+    }
+
+    /// Return to a saved state.  Nothing is recomputed.
+    pub fn restore(&mut self, snapshot: PossibilitiesSnapshot) {
+        *self = snapshot.0;
+    }
+
+    /// Like [`set`](Possibilities::set) for each `(row, col, digit)`, but
+    /// apply logic only once at the end.
+    pub fn set_batch(&mut self, clues: &[(usize, usize, u8)]) -> Result<(), ImpossiblePuzzle> {
+        for &(row, col, digit) in clues {
+            self.clues.push((row, col, digit));
+            self.enqueue_others((row, col), digit as usize - 1, Technique::Given);
+        }
         self.work()
     }
 
+    /// Mark the current state, to undo [`set`](Possibilities::set) and all
+    /// its consequences later.
+    ///
+    /// Unlike [`snapshot`](Possibilities::snapshot), this doesn't copy the
+    /// state.  Instead, every elimination from now on is remembered.
+    /// Checkpoints nest like a stack:  rolling back to or releasing one
+    /// invalidates any taken after it.  Once none are open, eliminations are
+    /// no longer remembered.
+    pub fn checkpoint(&mut self) -> Checkpoint {
+        let depth = self.open_checkpoints;
+        self.open_checkpoints += 1;
+        Checkpoint {
+            journal_len: self.journal.get_or_insert_with(Vec::new).len(),
+            clues_len: self.clues.len(),
+            depth,
+            work_queue: self.work_queue.clone(),
+            reasons: self
+                .eliminations
+                .as_ref()
+                .map(|log| (log.reasons.clone(), log.queued.clone())),
+        }
+    }
+
+    /// Undo everything since the checkpoint, even a `set` that failed.
+    ///
+    /// Work queued by [`set_deferred`](Possibilities::set_deferred) before
+    /// the checkpoint is queued again.
+    pub fn rollback(&mut self, checkpoint: Checkpoint) {
+        if let Some(journal) = &mut self.journal {
+            for (row, col, digit) in journal.drain(checkpoint.journal_len.min(journal.len())..) {
+                let pattern = &mut self.patterns[digit as usize];
+                *pattern = pattern.with(row as usize, col as usize);
+            }
+        }
+        self.clues.truncate(checkpoint.clues_len);
+        self.work_queue = checkpoint.work_queue;
+        if let Some(log) = &mut self.eliminations {
+            (log.reasons, log.queued) = checkpoint
+                .reasons
+                .unwrap_or_else(|| (Vec::new(), vec![None; self.work_queue.len()]));
+        }
+        self.close_checkpoints(checkpoint.depth);
+
+        // A failed `eliminate` leaves the counts half-updated, so rebuild them.
+        // The checkpointed state was consistent, so this can't fail.
+        let _ = self.recount();
+    }
+
+    /// Keep everything since the checkpoint, and stop remembering it.
+    pub fn release(&mut self, checkpoint: Checkpoint) {
+        self.close_checkpoints(checkpoint.depth);
+    }
+
+    /// Leave only the checkpoints taken before `depth` open.
+    fn close_checkpoints(&mut self, depth: usize) {
+        self.open_checkpoints = depth.min(self.open_checkpoints);
+        if self.open_checkpoints == 0 {
+            self.journal = None;
+        }
+    }
+
+    /// Apply queued logic until a cell is narrowed down to a single digit.
+    ///
+    /// Logic is usually applied all at once, so this only makes progress
+    /// after [`set_deferred`](Possibilities::set_deferred).  Calling it
+    /// repeatedly shows each deduction in turn.
+    pub fn step(&mut self) -> StepResult {
+        while !self.work_queue.is_empty() {
+            match self.work_one() {
+                Ok(Some((row, col))) => {
+                    let digit = self.find_in_cell(row, col) as u8 + 1;
+                    return StepResult::Placed(row, col, digit);
+                }
+                Ok(None) => {}
+                Err(ImpossiblePuzzle) => return StepResult::Contradiction,
+            }
+        }
+        if let Some(log) = &mut self.eliminations {
+            log.reasons.clear();
+        }
+        StepResult::Stable
+    }
+
     /// Run work queue until empty.
     fn work(&mut self) -> Result<(), ImpossiblePuzzle> {
-        while let Some((row, col, digit)) = self.work_queue.pop() {
-            self.eliminate(row as usize, col as usize, digit as usize)?;
+        while !self.work_queue.is_empty() {
+            self.work_one()?;
+        }
+        if let Some(log) = &mut self.eliminations {
+            log.reasons.clear();
         }
         Ok(())
     }
 
+    /// Run the next entry of the work queue.  Returns the cell, if it was
+    /// narrowed down to a single digit.
+    fn work_one(&mut self) -> Result<Option<(usize, usize)>, ImpossiblePuzzle> {
+        let Some((row, col, digit)) = self.work_queue.pop() else {
+            return Ok(None);
+        };
+        let (row, col, digit) = (row as usize, col as usize, digit as usize);
+        let present = self.patterns[digit].has(row, col);
+
+        if let Some(log) = &mut self.eliminations {
+            let reason = log.queued.pop().flatten();
+            if let Some(reason) = reason {
+                if present {
+                    let (technique, trigger_cells) = log.reasons[reason].clone();
+                    log.entries.push(CandidateElimination {
+                        row,
+                        col,
+                        digit: digit as u8 + 1,
+                        technique,
+                        trigger_cells,
+                    });
+                }
+            }
+        }
+        self.eliminate(row, col, digit)?;
+
+        let placed = present && self.cell_constraints[row][col] == 1;
+        Ok(placed.then_some((row, col)))
+    }
+
+    /// Give the reason for eliminations enqueued from now on, if recording.
+    fn note_reason(&mut self, technique: Technique, cells: Pattern) {
+        if let Some(log) = &mut self.eliminations {
+            log.reasons.push((technique, cells.cells().collect()));
+        }
+    }
+
     /// Enqueue removing a single digit from a cell.
     fn enqueue(&mut self, (row, col): (usize, usize), digit: usize) {
         self.work_queue.push((row as u8, col as u8, digit as u8));
+        if let Some(log) = &mut self.eliminations {
+            log.queued.push(log.reasons.len().checked_sub(1));
+        }
     }
     /// Enqueue removing all other digits from a cell.
-    fn enqueue_others(&mut self, (row, col): (usize, usize), digit: usize) {
+    fn enqueue_others(&mut self, (row, col): (usize, usize), digit: usize, technique: Technique) {
+        self.note_reason(technique, Pattern::bit(row, col));
         (0..9)
             .filter(|&d| d != digit)
             .for_each(|d| self.enqueue((row, col), d));
     }
     /// Enqueue removing this digit from all adjacent cells (rest of row, col, box).
     fn enqueue_adjacent(&mut self, (row, col): (usize, usize), digit: usize) {
+        self.note_reason(Technique::NakedSingle, Pattern::bit(row, col));
         for other_col in 0..9 {
             if other_col != col {
                 self.enqueue((row, other_col), digit);
@@ -130,10 +969,13 @@ impl Possibilities {
     /// Eliminate a digit, update constraints, and enqueue work if necessary.
     fn eliminate(&mut self, row: usize, col: usize, digit: usize) -> Result<(), ImpossiblePuzzle> {
         let old = self.patterns[digit].remove(row, col);
-        if old == false {
+        if !old {
             // digit already eliminated
             return Ok(());
         }
+        if let Some(journal) = &mut self.journal {
+            journal.push((row as u8, col as u8, digit as u8));
+        }
 
         self.cell_constraints[row][col] -= 1;
         match self.cell_constraints[row][col] {
@@ -145,14 +987,22 @@ impl Possibilities {
         self.row_constraints[row][digit] -= 1;
         match self.row_constraints[row][digit] {
             0 => return Err(ImpossiblePuzzle),
-            1 => self.enqueue_others((row, self.find_in_row(row, digit)), digit),
+            1 => {
+                let cell = (row, self.find_in_row(row, digit));
+                self.enqueue_others(cell, digit, Technique::HiddenSingle);
+                self.check_completed(Unit::Row(row), self.row_constraints[row]);
+            }
             2.. => {}
         }
 
         self.col_constraints[col][digit] -= 1;
         match self.col_constraints[col][digit] {
             0 => return Err(ImpossiblePuzzle),
-            1 => self.enqueue_others((self.find_in_col(col, digit), col), digit),
+            1 => {
+                let cell = (self.find_in_col(col, digit), col);
+                self.enqueue_others(cell, digit, Technique::HiddenSingle);
+                self.check_completed(Unit::Col(col), self.col_constraints[col]);
+            }
             2.. => {}
         }
 
@@ -160,13 +1010,26 @@ impl Possibilities {
         self.box_constraints[box_][digit] -= 1;
         match self.box_constraints[box_][digit] {
             0 => return Err(ImpossiblePuzzle),
-            1 => self.enqueue_others(self.find_in_box(row, col, digit), digit),
+            1 => {
+                let cell = self.find_in_box(row, col, digit);
+                self.enqueue_others(cell, digit, Technique::HiddenSingle);
+                self.check_completed(Unit::Box(box_), self.box_constraints[box_]);
+            }
             2.. => {}
         }
 
         Ok(())
     }
 
+    /// Record an event if every digit in the unit has been found.
+    fn check_completed(&mut self, unit: Unit, constraints: [u8; 9]) {
+        if let Some(events) = &mut self.events {
+            if constraints == [1; 9] {
+                events.push(PropagationEvent::UnitCompleted(unit));
+            }
+        }
+    }
+
     /// Find unique digit in cell.
     fn find_in_cell(&self, row: usize, col: usize) -> usize {
         (0..9)
@@ -193,9 +1056,125 @@ impl Possibilities {
             .expect("no digit in box")
     }
 
+    /// Cells with exactly `n` remaining digits, as `(row, col)`.
+    pub fn cells_with_n_candidates(&self, n: u8) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (0..81)
+            .map(|i| (i / 9, i % 9))
+            .filter(move |&(row, col)| self.cell_constraints[row][col] == n)
+    }
+
+    /// Cells with exactly two remaining digits.
+    pub fn bivalue_cells(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.cells_with_n_candidates(2)
+    }
+
+    /// JSON object with a 9×9 array of sorted possible digits:
+    /// `{"candidates": [[[1,3,5],...],...]}`.
+    pub fn fmt_json(&self) -> String {
+        let rows: Vec<String> = (0..9)
+            .map(|row| {
+                let cells: Vec<String> = (0..9)
+                    .map(|col| {
+                        let digits: Vec<String> = (0..9)
+                            .filter(|&digit| self.patterns[digit].has(row, col))
+                            .map(|digit| (digit + 1).to_string())
+                            .collect();
+                        format!("[{}]", digits.join(","))
+                    })
+                    .collect();
+                format!("[{}]", cells.join(","))
+            })
+            .collect();
+        format!("{{\"candidates\":[{}]}}", rows.join(","))
+    }
+
+    /// Bit mask of digits possible in the cell.  Bit `d` means digit `d + 1`.
+    pub fn candidate_mask(&self, row: usize, col: usize) -> u16 {
+        (0..9)
+            .filter(|&digit| self.patterns[digit].has(row, col))
+            .fold(0, |mask, digit| mask | 1 << digit)
+    }
+
+    /// Per-cell bit masks of possible digits.  Bit `d` means digit `d + 1`.
+    pub fn candidate_masks(&self) -> [[u16; 9]; 9] {
+        std::array::from_fn(|row| std::array::from_fn(|col| self.candidate_mask(row, col)))
+    }
+
+    /// Digit (0-indexed) with the fewest possible templates.
+    ///
+    /// `None` if some digit has no possible templates.
+    pub fn most_constrained_digit(&self) -> Option<usize> {
+        let counts = self.remaining_template_counts();
+        if counts.contains(&0) {
+            return None;
+        }
+        (0..9).min_by_key(|&digit| counts[digit])
+    }
+
+    /// Digit (0-indexed) with the most possible templates.
+    ///
+    /// `None` if some digit has no possible templates.
+    pub fn least_constrained_digit(&self) -> Option<usize> {
+        let counts = self.remaining_template_counts();
+        if counts.contains(&0) {
+            return None;
+        }
+        (0..9).max_by_key(|&digit| counts[digit])
+    }
+
+    /// Number of possible templates for each digit, 0-indexed.
+    pub fn remaining_template_counts(&self) -> [usize; 9] {
+        self.patterns.map(|p| Template::within(p).count())
+    }
+
+    /// Product of [`remaining_template_counts`](Possibilities::remaining_template_counts),
+    /// saturating at `u64::MAX`.  An upper bound on the size of the search.
+    ///
+    /// 1 if solved, 0 if some digit has no templates.
+    pub fn remaining_template_product(&self) -> u64 {
+        self.remaining_template_counts()
+            .iter()
+            .fold(1u64, |acc, &count| acc.saturating_mul(count as u64))
+    }
+
+    /// Do the puzzles have the same possible digits in every cell?
+    ///
+    /// Ignores how each state was reached.
+    pub fn is_equivalent_to(&self, other: &Possibilities) -> bool {
+        self.patterns == other.patterns && self.cell_constraints == other.cell_constraints
+    }
+
+    /// Logical state, ignoring the work queue and history.
+    fn logical_state(&self) -> impl Ord + std::hash::Hash + '_ {
+        (
+            &self.patterns,
+            &self.cell_constraints,
+            &self.row_constraints,
+            &self.col_constraints,
+            &self.box_constraints,
+        )
+    }
+
+    /// Does some full solution put `digit` (1 through 9) in this cell?
+    ///
+    /// Unlike [`patterns_for_digit`](Possibilities::patterns_for_digit),
+    /// which only reflects the logic applied so far, this searches.
+    pub fn digit_possible_somewhere(&self, row: usize, col: usize, digit: u8) -> bool {
+        if !self.patterns_for_digit(digit).has(row, col) {
+            return false; // already eliminated
+        }
+
+        let mut fixed = self.branch();
+        if fixed.set(row, col, digit).is_err() {
+            return false;
+        }
+        !SearchTree::new(&fixed).max_solutions(1).run().is_empty()
+    }
+
     /// If the solution is unique, return it.
     pub fn unique(&self) -> Option<Solution> {
         let mut solution = Solution::default();
+        let mut filled = Pattern::EMPTY;
 
         for digit in 0..9 {
             let mut iter = Template::within(self.patterns[digit]);
@@ -205,9 +1184,116 @@ impl Possibilities {
                 // not unique
                 return None;
             }
+
+            // same check as `Solution::is_valid`, but without a second pass
+            let pattern = solution.0[digit].as_pattern();
+            if pattern.intersects(filled) {
+                return None;
+            }
+            filled = filled | pattern;
         }
 
-        Some(solution).filter(|s| s.is_valid())
+        Some(solution)
+    }
+}
+
+// Comparisons only look at the logical state.  Two puzzles which set the same
+// clues in a different order have different work queues and histories, but
+// they are the same puzzle.
+
+impl PartialEq for Possibilities {
+    fn eq(&self, other: &Self) -> bool {
+        self.logical_state() == other.logical_state()
+    }
+}
+
+impl Eq for Possibilities {}
+
+impl PartialOrd for Possibilities {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Possibilities {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.logical_state().cmp(&other.logical_state())
+    }
+}
+
+impl Default for Possibilities {
+    fn default() -> Possibilities {
+        Possibilities::new()
+    }
+}
+
+impl std::hash::Hash for Possibilities {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.logical_state().hash(state)
+    }
+}
+
+impl AsRef<[Pattern; 9]> for Possibilities {
+    fn as_ref(&self) -> &[Pattern; 9] {
+        &self.patterns
+    }
+}
+
+impl TryFrom<[[u8; 9]; 9]> for Possibilities {
+    type Error = ImpossiblePuzzle;
+
+    /// Prepare a puzzle from a grid of digits, where 0 is an empty cell.
+    fn try_from(input: [[u8; 9]; 9]) -> Result<Possibilities, ImpossiblePuzzle> {
+        let mut puzzle = Possibilities::new();
+
+        for (row, digits) in input.iter().enumerate() {
+            for (col, &digit) in digits.iter().enumerate() {
+                if digit > 0 {
+                    puzzle.set(row, col, digit)?;
+                }
+            }
+        }
+
+        Ok(puzzle)
+    }
+}
+
+impl TryFrom<[[u16; 9]; 9]> for Possibilities {
+    type Error = ImpossiblePuzzle;
+
+    /// Keep only the digits in each cell's mask, and apply logic.  Bit `d`
+    /// means digit `d + 1`, as in [`Possibilities::candidate_masks`].
+    fn try_from(masks: [[u16; 9]; 9]) -> Result<Possibilities, ImpossiblePuzzle> {
+        let mut puzzle = Possibilities::new();
+
+        for (row, row_masks) in masks.iter().enumerate() {
+            for (col, &mask) in row_masks.iter().enumerate() {
+                for digit in 0..9 {
+                    if mask & 1 << digit == 0 {
+                        puzzle.enqueue((row, col), digit);
+                    }
+                }
+            }
+        }
+
+        puzzle.work()?;
+        Ok(puzzle)
+    }
+}
+
+impl From<&Possibilities> for [[u16; 9]; 9] {
+    /// See [`Possibilities::candidate_masks`].
+    fn from(puzzle: &Possibilities) -> [[u16; 9]; 9] {
+        puzzle.candidate_masks()
+    }
+}
+
+impl TryFrom<&str> for Possibilities {
+    type Error = ParseError;
+
+    /// Prepare a puzzle from text.  See [`parse`](crate::parse).
+    fn try_from(input: &str) -> Result<Possibilities, ParseError> {
+        Ok(Possibilities::try_from(parse(input)?)?)
     }
 }
 
@@ -216,13 +1302,13 @@ const fn box_cells(row: usize, col: usize) -> [(usize, usize); 9] {
     let great_row = row / 3;
     let great_col = col / 3;
     [
-        (3 * great_row + 0, 3 * great_col + 0),
-        (3 * great_row + 0, 3 * great_col + 1),
-        (3 * great_row + 0, 3 * great_col + 2),
-        (3 * great_row + 1, 3 * great_col + 0),
+        (3 * great_row, 3 * great_col),
+        (3 * great_row, 3 * great_col + 1),
+        (3 * great_row, 3 * great_col + 2),
+        (3 * great_row + 1, 3 * great_col),
         (3 * great_row + 1, 3 * great_col + 1),
         (3 * great_row + 1, 3 * great_col + 2),
-        (3 * great_row + 2, 3 * great_col + 0),
+        (3 * great_row + 2, 3 * great_col),
         (3 * great_row + 2, 3 * great_col + 1),
         (3 * great_row + 2, 3 * great_col + 2),
     ]
@@ -230,9 +1316,19 @@ const fn box_cells(row: usize, col: usize) -> [(usize, usize); 9] {
 
 impl std::fmt::Display for Possibilities {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        // Possibilities grid, each Sudoku cell separated with `|`
+        // Possibilities grid, each Sudoku cell separated with `|` and each box
+        // with `||`.  Every cell is 9 characters wide, so every line is too.
+        const WIDTH: usize = 9 * 9 + 6 + 2 * 2;
         for row in 0..9 {
+            if row == 3 || row == 6 {
+                writeln!(f, "{}", "=".repeat(WIDTH))?;
+            }
             for col in 0..9 {
+                if col == 3 || col == 6 {
+                    write!(f, "||")?;
+                } else if col > 0 {
+                    write!(f, "|")?;
+                }
                 for digit in 0..9 {
                     if self.patterns[digit].has(row, col) {
                         write!(f, "{}", digit + 1)?;
@@ -240,12 +1336,740 @@ impl std::fmt::Display for Possibilities {
                         write!(f, " ")?;
                     }
                 }
-                if col < 8 {
-                    write!(f, "|")?;
-                }
             }
-            write!(f, "\n")?;
+            writeln!(f)?;
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EASY_SOLVED: &str =
+        "534678912672195348198342567859761423426853791713924856961537284287419635345286179";
+    const HARD: &str =
+        "4.....8.5.3..........7......2.....6.....8.4......1.......6.3.7.5..2.....1.4......";
+
+    #[test]
+    fn cells_by_candidate_count() {
+        let mut puzzle = Possibilities::new();
+        assert_eq!(puzzle.cells_with_n_candidates(1).count(), 0);
+        assert_eq!(puzzle.cells_with_n_candidates(9).count(), 81);
+        assert_eq!(puzzle.bivalue_cells().count(), 0);
+
+        puzzle.set(0, 0, 1).unwrap();
+        assert_eq!(
+            puzzle.cells_with_n_candidates(1).collect::<Vec<_>>(),
+            [(0, 0)]
+        );
+        assert_eq!(puzzle.cells_with_n_candidates(8).count(), 20);
+    }
+
+    #[test]
+    fn patterns_for_digit_is_one_indexed() {
+        let mut puzzle = Possibilities::try_from(HARD).unwrap();
+        assert_eq!(puzzle.patterns_for_digit(5), puzzle.patterns[4]);
+        *puzzle.patterns_for_digit_mut(9) = Pattern::EMPTY;
+        assert_eq!(puzzle.patterns[8], Pattern::EMPTY);
+    }
+
+    #[test]
+    #[should_panic(expected = "digit 0 out of range 1..=9")]
+    fn patterns_for_digit_rejects_0() {
+        Possibilities::new().patterns_for_digit(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "digit 10 out of range 1..=9")]
+    fn patterns_for_digit_rejects_10() {
+        Possibilities::new().patterns_for_digit_mut(10);
+    }
+
+    #[test]
+    fn constraint_counts_follow_set() {
+        let mut puzzle = Possibilities::new();
+        let fresh = puzzle.constraint_counts();
+        for counts in [fresh.cell, fresh.row, fresh.col, fresh.box_] {
+            assert_eq!(counts, [[9; 9]; 9]);
+        }
+
+        puzzle.set(0, 0, 1).unwrap();
+        let counts = puzzle.constraint_counts();
+        assert_eq!(counts.cell[0][0], 1);
+        assert_eq!(
+            (counts.row[0][0], counts.col[0][0], counts.box_[0][0]),
+            (1, 1, 1)
+        );
+        for digit in 1..9 {
+            assert_eq!(counts.row[0][digit], 8);
+            assert_eq!(counts.col[0][digit], 8);
+            assert_eq!(counts.box_[0][digit], 8);
+        }
+        puzzle.check_invariants();
+    }
+
+    #[test]
+    fn json_candidates() {
+        use crate::json::{parse, Value};
+
+        let mut puzzle = Possibilities::new();
+        puzzle.set(0, 0, 5).unwrap();
+        let Some(Value::Object(fields)) = parse(&puzzle.fmt_json()) else {
+            panic!("not a JSON object");
+        };
+        let [(key, Value::Array(rows))] = &fields[..] else {
+            panic!("expected one field");
+        };
+        assert_eq!(key, "candidates");
+        assert_eq!(rows.len(), 9);
+
+        let cell = |row: usize, col: usize| match &rows[row] {
+            Value::Array(cells) => &cells[col],
+            _ => panic!("row isn't an array"),
+        };
+        assert_eq!(*cell(0, 0), Value::Array(vec![Value::Number(5)]));
+        let peer: Vec<_> = [1, 2, 3, 4, 6, 7, 8, 9].map(Value::Number).into();
+        assert_eq!(*cell(0, 8), Value::Array(peer));
+        assert_eq!(
+            *cell(8, 8),
+            Value::Array((1..=9).map(Value::Number).collect())
+        );
+    }
+
+    /// The hard puzzle with its clues given in order, and in reverse.
+    fn forwards_and_backwards() -> (Possibilities, Possibilities) {
+        let clues = Possibilities::try_from(HARD).unwrap().clues();
+        let mut forwards = Possibilities::new();
+        let mut backwards = Possibilities::new();
+        for &(row, col, digit) in &clues {
+            forwards.set(row, col, digit).unwrap();
+        }
+        for &(row, col, digit) in clues.iter().rev() {
+            backwards.set(row, col, digit).unwrap();
+        }
+        (forwards, backwards)
+    }
+
+    #[test]
+    fn equality_ignores_clue_order() {
+        let (forwards, mut backwards) = forwards_and_backwards();
+        assert_ne!(forwards.clues, backwards.clues);
+        assert!(forwards.is_equivalent_to(&backwards));
+        assert_eq!(forwards, backwards);
+
+        // already implied, so only the queue differs
+        let (row, col, digit) = forwards.clues[0];
+        backwards.set_deferred(row, col, digit);
+        assert!(!backwards.work_queue.is_empty());
+        assert!(forwards.is_equivalent_to(&backwards));
+        assert_eq!(forwards, backwards);
+
+        assert_ne!(forwards, Possibilities::new());
+        assert!(!forwards.is_equivalent_to(&Possibilities::new()));
+    }
+
+    #[test]
+    fn hash_ignores_clue_order() {
+        use std::collections::HashSet;
+        use std::hash::{BuildHasher, RandomState};
+
+        let (forwards, mut backwards) = forwards_and_backwards();
+        let (row, col, digit) = forwards.clues[0];
+        backwards.set_deferred(row, col, digit);
+
+        let state = RandomState::new();
+        assert_eq!(state.hash_one(&forwards), state.hash_one(&backwards));
+
+        let set: HashSet<_> = [forwards, backwards, Possibilities::new()].into();
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn locked_candidates_in_both_directions() {
+        let puzzle = Possibilities::try_from(
+            ".8..46..9.1..794....9..3..2...7..95.....8...4.7....6.88..65.1.33.....84..964.8..7",
+        )
+        .unwrap();
+
+        let mut locked = puzzle.clone();
+        locked.record_eliminations();
+        assert!(locked.apply_locked_candidates().unwrap());
+        let techniques: Vec<_> = locked
+            .take_eliminations()
+            .into_iter()
+            .map(|entry| entry.technique)
+            .collect();
+        assert!(techniques.contains(&Technique::PointingPair));
+        assert!(techniques.contains(&Technique::BoxLineReduction));
+
+        // each direction alone does less
+        let mut pointing = puzzle.clone();
+        let mut box_line = puzzle.clone();
+        assert!(pointing.apply_pointing_pairs().unwrap());
+        assert!(box_line.apply_box_line_reduction().unwrap());
+        for digit in 0..9 {
+            assert!(locked.patterns[digit].is_subset(pointing.patterns[digit]));
+            assert!(locked.patterns[digit].is_subset(box_line.patterns[digit]));
+        }
+        assert_ne!(locked, pointing);
+        assert_ne!(locked, box_line);
+    }
+
+    #[test]
+    fn most_constrained_unit() {
+        assert_eq!(
+            Possibilities::new().most_constrained_unit(),
+            Some((Unit::Row(0), 1))
+        );
+
+        // 4 has two places left in column 5
+        let mut masks = [[0x1FFu16; 9]; 9];
+        for row_masks in &mut masks[2..] {
+            row_masks[5] &= !(1 << 3);
+        }
+        let puzzle = Possibilities::try_from(masks).unwrap();
+        let (unit, digit) = puzzle.most_constrained_unit().unwrap();
+        assert_eq!((unit, digit), (Unit::Col(5), 4));
+        let cells = puzzle.patterns_for_digit(digit) & Pattern::unit_mask(unit);
+        assert_eq!(cells.count(), 2);
+
+        let solved = Possibilities::try_from(EASY_SOLVED).unwrap();
+        assert_eq!(solved.most_constrained_unit(), None);
+    }
+
+    /// X-wing, written out directly:  candidate masks after removing the
+    /// digit wherever two rows confine it to two columns, or vice versa.
+    fn x_wing_reference(puzzle: &Possibilities) -> [[u16; 9]; 9] {
+        let mut masks = puzzle.candidate_masks();
+        for digit in 0..9 {
+            let has = |row, col| puzzle.patterns[digit].has(row, col);
+            for transpose in [false, true] {
+                let has = |i, j| if transpose { has(j, i) } else { has(i, j) };
+                let lines: [u16; 9] = std::array::from_fn(|i| {
+                    (0..9).filter(|&j| has(i, j)).fold(0, |m, j| m | 1 << j)
+                });
+                for a in 0..9 {
+                    for b in a + 1..9 {
+                        let covered = lines[a] | lines[b];
+                        if lines[a] == 0 || lines[b] == 0 || covered.count_ones() != 2 {
+                            continue;
+                        }
+                        for i in (0..9).filter(|&i| i != a && i != b) {
+                            for j in (0..9).filter(|&j| covered & 1 << j != 0) {
+                                let (row, col) = if transpose { (j, i) } else { (i, j) };
+                                masks[row][col] &= !(1 << digit);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        masks
+    }
+
+    #[test]
+    fn fish_of_size_2_is_x_wing() {
+        let mut rng = crate::test_rng(3);
+        let mut progress = 0;
+        for _ in 0..20 {
+            let solution = <[[u8; 9]; 9]>::from(&Solution::sample_random(&mut rng));
+            for _ in 0..20 {
+                let mut grid = solution;
+                for _ in 0..55 {
+                    grid[rng(9)][rng(9)] = 0;
+                }
+                let puzzle = Possibilities::try_from(grid).unwrap();
+
+                let mut fish = puzzle.clone();
+                progress += fish.apply_naked_fish_general(2).unwrap() as usize;
+                let x_wing = Possibilities::try_from(x_wing_reference(&puzzle)).unwrap();
+                assert_eq!(fish, x_wing);
+            }
+        }
+        assert!(progress > 0);
+    }
+
+    #[test]
+    fn digit_forcing_chain_goes_further() {
+        let puzzle = Possibilities::try_from(
+            "8.2.17..47.3..6....94..37...3..9.1.7.8..3125.1.9..26.3...1.43.....27......8....12",
+        )
+        .unwrap();
+
+        // simpler techniques are stuck
+        let mut simple = puzzle.clone();
+        assert!(!simple.apply_locked_candidates().unwrap());
+        for size in 2..=4 {
+            assert!(!simple.apply_naked_fish_general(size).unwrap());
+        }
+
+        let mut chained = puzzle.clone();
+        assert!(chained.apply_digit_forcing_chain(4).unwrap());
+        let solutions = SearchTree::new(&puzzle).max_solutions(2).run();
+        assert_eq!(solutions.len(), 1);
+        let solution = <[[u8; 9]; 9]>::from(&solutions[0]);
+        for (row, digits) in solution.iter().enumerate() {
+            for (col, &digit) in digits.iter().enumerate() {
+                assert!(chained.patterns_for_digit(digit).has(row, col));
+            }
+        }
+    }
+
+    #[test]
+    fn template_sets_round_trip() {
+        let puzzle = Possibilities::try_from(HARD).unwrap();
+        let templates = puzzle.as_template_sets();
+        let tightened = Possibilities::from_template_sets(&templates).unwrap();
+
+        // candidates outside every template are dropped, and this puzzle has some
+        assert_ne!(tightened.patterns, puzzle.patterns);
+        for digit in 0..9 {
+            assert!(tightened.patterns[digit].is_subset(puzzle.patterns[digit]));
+        }
+        assert_eq!(tightened.as_template_sets(), templates);
+
+        // after that, the round trip is exact
+        let again = Possibilities::from_template_sets(&tightened.as_template_sets()).unwrap();
+        assert_eq!(again.patterns, tightened.patterns);
+    }
+
+    #[test]
+    fn template_sets_must_cover_every_cell() {
+        let mut templates = Possibilities::new().as_template_sets();
+        templates[0].clear();
+        assert!(Possibilities::from_template_sets(&templates).is_none());
+    }
+
+    #[test]
+    fn logged_eliminations_are_gone() {
+        let mut puzzle = Possibilities::new();
+        puzzle.record_eliminations();
+        for (row, col, digit) in Possibilities::try_from(HARD).unwrap().clues() {
+            puzzle.set(row, col, digit).unwrap();
+        }
+        puzzle.apply_locked_candidates().unwrap();
+        puzzle.apply_naked_fish_general(2).unwrap();
+
+        let log = puzzle.take_eliminations();
+        assert!(!log.is_empty());
+        for entry in log {
+            assert!(!puzzle
+                .patterns_for_digit(entry.digit)
+                .has(entry.row, entry.col));
+            assert!(!entry.trigger_cells.is_empty());
+        }
+    }
+
+    #[test]
+    fn counts_match_patterns_after_random_sets() {
+        for seed in 1..=200 {
+            let mut rng = crate::test_rng(seed);
+            let mut puzzle = Possibilities::new();
+            puzzle.check_invariants();
+
+            loop {
+                let solved: Vec<_> = puzzle.cells_with_n_candidates(1).collect();
+                let unsolved: Vec<_> = (0..81)
+                    .map(|i| (i / 9, i % 9))
+                    .filter(|cell| !solved.contains(cell))
+                    .collect();
+                if unsolved.is_empty() {
+                    break;
+                }
+
+                let (row, col) = unsolved[rng(unsolved.len())];
+                let mask = puzzle.candidate_mask(row, col);
+                let digits: Vec<u8> = (1..=9).filter(|d| mask & 1 << (d - 1) != 0).collect();
+                let digit = digits[rng(digits.len())];
+
+                if puzzle.set(row, col, digit).is_err() {
+                    break;
+                }
+                puzzle.check_invariants();
+            }
+        }
+    }
+
+    #[test]
+    fn hidden_single_in_all_units_places_the_only_single() {
+        // 1 only fits at r1c5 in the first row; nothing else is known
+        let mut masks = [[0x1FFu16; 9]; 9];
+        for (col, mask) in masks[0].iter_mut().enumerate() {
+            if col != 4 {
+                *mask &= !1;
+            }
+        }
+        let propagated = Possibilities::try_from(masks).unwrap();
+        assert_eq!(propagated.get(0, 4), Some(1));
+
+        let mut scanned = Possibilities::new();
+        *scanned.patterns_for_digit_mut(1) = !Pattern::row_mask(0) | Pattern::bit(0, 4);
+        assert!(scanned.work_queue.is_empty());
+        assert_eq!(scanned.get(0, 4), None);
+
+        assert!(scanned.apply_hidden_single_in_all_units().unwrap());
+        assert_eq!(scanned.get(0, 4), Some(1));
+        assert_eq!(scanned, propagated);
+        assert_eq!(scanned.constraint_counts(), propagated.constraint_counts());
+
+        // nothing left to find
+        assert!(!scanned.apply_hidden_single_in_all_units().unwrap());
+        assert_eq!(scanned, propagated);
+    }
+
+    #[test]
+    fn exchange_format_round_trip() {
+        let puzzle = Possibilities::try_from(HARD).unwrap();
+        let text = puzzle.to_sudoku_exchange_format();
+        assert_eq!(text, HARD.replace('.', "0"));
+
+        let read = Possibilities::from_sudoku_exchange_format(&text).unwrap();
+        assert_eq!(read, puzzle);
+        assert_eq!(read.clues(), puzzle.clues());
+        assert_eq!(read.to_sudoku_exchange_format(), text);
+
+        let line = format!("{} 1a2b3c4d 5.25", text);
+        assert_eq!(
+            Possibilities::from_sudoku_exchange_format(&line),
+            Ok(puzzle)
+        );
+    }
+
+    #[test]
+    fn solution_exchange_format_reads_as_possibilities() {
+        let solution = Solution::try_from(EASY_SOLVED).unwrap();
+        let text = solution.to_sudoku_exchange_format();
+
+        let puzzle = Possibilities::from_sudoku_exchange_format(&text).unwrap();
+        assert_eq!(puzzle.clues().len(), 81);
+        assert_eq!(puzzle.unique(), Some(solution));
+        assert_eq!(Possibilities::try_from(text.as_str()), Ok(puzzle.clone()));
+        assert_eq!(puzzle.to_sudoku_exchange_format(), text);
+    }
+
+    #[test]
+    fn rollback_undoes_set() {
+        let original = Possibilities::try_from(HARD).unwrap();
+        let mut puzzle = original.clone();
+        let checkpoint = puzzle.checkpoint();
+        puzzle.set(1, 0, 6).unwrap();
+        assert_ne!(puzzle, original);
+
+        puzzle.rollback(checkpoint);
+        assert_eq!(puzzle, original);
+        assert_eq!(puzzle.clues(), original.clues());
+        assert_eq!(puzzle.constraint_counts(), original.constraint_counts());
+    }
+
+    #[test]
+    fn rollback_keeps_work_deferred_before_checkpoint() {
+        let mut puzzle = Possibilities::try_from(HARD).unwrap();
+        puzzle.set_deferred(1, 0, 6);
+        let deferred = puzzle.clone();
+
+        let checkpoint = puzzle.checkpoint();
+        puzzle.set(2, 0, 9).unwrap();
+        assert!(puzzle.work_queue.is_empty());
+        puzzle.rollback(checkpoint);
+        assert_eq!(puzzle.work_queue, deferred.work_queue);
+        assert_eq!(puzzle.clues(), deferred.clues());
+
+        let mut expected = Possibilities::try_from(HARD).unwrap();
+        expected.set(1, 0, 6).unwrap();
+        while puzzle.step() != StepResult::Stable {}
+        assert_eq!(puzzle, expected);
+    }
+
+    #[test]
+    fn journal_stops_once_no_checkpoint_is_open() {
+        let mut puzzle = Possibilities::try_from(HARD).unwrap();
+        let checkpoint = puzzle.checkpoint();
+        puzzle.set(1, 0, 6).unwrap();
+        puzzle.rollback(checkpoint);
+        assert!(puzzle.journal.is_none());
+
+        let checkpoint = puzzle.checkpoint();
+        puzzle.set(1, 0, 6).unwrap();
+        puzzle.release(checkpoint);
+        assert!(puzzle.journal.is_none());
+        puzzle.set(2, 0, 9).unwrap();
+        assert!(puzzle.journal.is_none());
+        assert_eq!(puzzle.get(1, 0), Some(6));
+
+        // nested:  releasing the inner checkpoint keeps the outer one working
+        let mut puzzle = Possibilities::try_from(HARD).unwrap();
+        let original = puzzle.clone();
+        let outer = puzzle.checkpoint();
+        puzzle.set(1, 0, 6).unwrap();
+        let inner = puzzle.checkpoint();
+        puzzle.set(2, 0, 9).unwrap();
+        puzzle.release(inner);
+        assert!(puzzle.journal.is_some());
+        puzzle.rollback(outer);
+        assert_eq!(puzzle, original);
+        assert!(puzzle.journal.is_none());
+    }
+
+    #[test]
+    fn steps_add_up_to_work() {
+        let mut stepped = Possibilities::new();
+        for (row, digits) in parse(HARD).unwrap().iter().enumerate() {
+            for (col, &digit) in digits.iter().enumerate() {
+                if digit > 0 {
+                    stepped.set_deferred(row, col, digit);
+                }
+            }
+        }
+        let mut worked = stepped.clone();
+        worked.work().unwrap();
+
+        let mut placed = 0;
+        loop {
+            match stepped.step() {
+                StepResult::Placed(row, col, digit) => {
+                    assert_eq!(stepped.get(row, col), Some(digit));
+                    placed += 1;
+                }
+                StepResult::Stable => break,
+                StepResult::Contradiction => panic!("contradiction"),
+            }
+        }
+        assert!(placed >= 17);
+        assert_eq!(stepped, worked);
+        assert_eq!(stepped.constraint_counts(), worked.constraint_counts());
+
+        // no progress left
+        assert_eq!(stepped.step(), StepResult::Stable);
+        assert_eq!(stepped, worked);
+    }
+
+    #[test]
+    fn restore_snapshot_is_idempotent() {
+        let mut puzzle = Possibilities::try_from(HARD).unwrap();
+        let original = puzzle.clone();
+        let snapshot = puzzle.snapshot();
+
+        puzzle.restore(snapshot.clone());
+        assert_eq!(puzzle, original);
+        assert_eq!(puzzle.clues(), original.clues());
+        puzzle.restore(snapshot.clone());
+        assert_eq!(puzzle, original);
+
+        puzzle.set(1, 0, 6).unwrap();
+        assert_ne!(puzzle, original);
+        puzzle.restore(snapshot.clone());
+        puzzle.restore(snapshot);
+        assert_eq!(puzzle, original);
+        assert_eq!(puzzle.constraint_counts(), original.constraint_counts());
+    }
+
+    #[test]
+    fn most_and_least_constrained_digit() {
+        let mut puzzle = Possibilities::new();
+        for (row, col) in [(0, 0), (1, 3), (2, 6), (3, 1)] {
+            puzzle.set(row, col, 3).unwrap();
+        }
+        puzzle.set(8, 8, 7).unwrap();
+
+        let counts = puzzle.remaining_template_counts();
+        assert_eq!(puzzle.most_constrained_digit(), Some(2));
+        assert!((0..9).all(|digit| digit == 2 || counts[digit] > counts[2]));
+        // besides 3 and 7, the digits tie, and the last one wins
+        assert_eq!(puzzle.least_constrained_digit(), Some(8));
+        assert!(counts[6] < counts[8]);
+        assert_eq!(counts[0], counts[8]);
+
+        *puzzle.patterns_for_digit_mut(5) = Pattern::EMPTY;
+        assert_eq!(puzzle.most_constrained_digit(), None);
+        assert_eq!(puzzle.least_constrained_digit(), None);
+    }
+
+    #[test]
+    fn constraint_propagation_round_is_idempotent() {
+        let converged = Possibilities::try_from(HARD).unwrap();
+        let mut puzzle = converged.clone();
+        assert!(!puzzle.apply_constraint_propagation_round().unwrap());
+        assert_eq!(puzzle, converged);
+        assert_eq!(puzzle.constraint_counts(), converged.constraint_counts());
+
+        // 1 is left only at r1c2 in the first row
+        let ones = puzzle.patterns_for_digit_mut(1);
+        *ones = (*ones & !Pattern::row_mask(0)) | Pattern::bit(0, 1);
+        assert!(puzzle.apply_constraint_propagation_round().unwrap());
+        assert_eq!(puzzle.get(0, 1), Some(1));
+
+        let once = puzzle.clone();
+        assert!(!puzzle.apply_constraint_propagation_round().unwrap());
+        assert_eq!(puzzle, once);
+        assert_eq!(puzzle.constraint_counts(), once.constraint_counts());
+    }
+
+    #[test]
+    fn set_batch_matches_set() {
+        for input in [HARD, EASY_SOLVED] {
+            let one_by_one = Possibilities::try_from(input).unwrap();
+            let mut batched = Possibilities::new();
+            batched.set_batch(&one_by_one.clues()).unwrap();
+            assert_eq!(batched, one_by_one);
+            assert_eq!(batched.clues(), one_by_one.clues());
+            assert_eq!(batched.constraint_counts(), one_by_one.constraint_counts());
+        }
+
+        // on top of earlier clues
+        let mut batched = Possibilities::try_from(HARD).unwrap();
+        let mut one_by_one = batched.clone();
+        let more = [(1, 0, 6), (2, 0, 9), (8, 8, 3)];
+        batched.set_batch(&more).unwrap();
+        for (row, col, digit) in more {
+            one_by_one.set(row, col, digit).unwrap();
+        }
+        assert_eq!(batched, one_by_one);
+
+        let mut conflict = Possibilities::new();
+        assert!(conflict.set_batch(&[(0, 0, 1), (0, 8, 1)]).is_err());
+    }
+
+    /// Fresh puzzle with 1 removed from `cells`, and logic applied.
+    fn without_ones(cells: Pattern) -> Possibilities {
+        let mut masks = [[0x1FFu16; 9]; 9];
+        for (row, col) in cells.cells() {
+            masks[row][col] &= !1;
+        }
+        Possibilities::try_from(masks).unwrap()
+    }
+
+    #[test]
+    fn pointing_pairs_alone() {
+        // in the first box, 1 is only in the first row
+        let box_rest = Pattern::box_mask(0) & !Pattern::row_mask(0);
+        let puzzle = without_ones(box_rest);
+        let row_rest = Pattern::row_mask(0) & !Pattern::box_mask(0);
+
+        let mut pointing = puzzle.clone();
+        pointing.record_eliminations();
+        assert!(pointing.apply_pointing_pairs().unwrap());
+        assert_eq!(pointing.patterns_for_digit(1), !(box_rest | row_rest));
+        let log = pointing.take_eliminations();
+        assert_eq!(log.len(), 6);
+        for entry in log {
+            assert_eq!(entry.technique, Technique::PointingPair);
+            assert!(row_rest.has(entry.row, entry.col));
+        }
+        assert!(!pointing.apply_pointing_pairs().unwrap());
+
+        let mut box_line = puzzle.clone();
+        assert!(!box_line.apply_box_line_reduction().unwrap());
+        assert_eq!(box_line, puzzle);
+    }
+
+    #[test]
+    fn box_line_reduction_alone() {
+        // in the first row, 1 is only in the first box
+        let row_rest = Pattern::row_mask(0) & !Pattern::box_mask(0);
+        let puzzle = without_ones(row_rest);
+        let box_rest = Pattern::box_mask(0) & !Pattern::row_mask(0);
+
+        let mut box_line = puzzle.clone();
+        box_line.record_eliminations();
+        assert!(box_line.apply_box_line_reduction().unwrap());
+        assert_eq!(box_line.patterns_for_digit(1), !(box_rest | row_rest));
+        let log = box_line.take_eliminations();
+        assert_eq!(log.len(), 6);
+        for entry in log {
+            assert_eq!(entry.technique, Technique::BoxLineReduction);
+            assert!(box_rest.has(entry.row, entry.col));
+        }
+        assert!(!box_line.apply_box_line_reduction().unwrap());
+
+        let mut pointing = puzzle.clone();
+        assert!(!pointing.apply_pointing_pairs().unwrap());
+        assert_eq!(pointing, puzzle);
+    }
+
+    #[test]
+    fn union_and_intersection_patterns() {
+        let mut puzzle = Possibilities::new();
+        assert_eq!(puzzle.union_patterns(), Pattern::FULL);
+        assert_eq!(puzzle.intersection_patterns(), Pattern::FULL);
+
+        puzzle.set(0, 0, 1).unwrap();
+        let touched = Pattern::peers(0).with(0, 0);
+        assert_eq!(puzzle.union_patterns(), Pattern::FULL);
+        assert_eq!(puzzle.intersection_patterns(), !touched);
+
+        let solved = Possibilities::try_from(EASY_SOLVED).unwrap();
+        assert_eq!(solved.union_patterns(), Pattern::FULL);
+        assert_eq!(solved.intersection_patterns(), Pattern::EMPTY);
+
+        // a cell with no digits left
+        for digit in 1..=9 {
+            let pattern = puzzle.patterns_for_digit_mut(digit);
+            *pattern = *pattern & !Pattern::bit(8, 8);
+        }
+        assert_eq!(puzzle.union_patterns(), !Pattern::bit(8, 8));
+    }
+
+    #[test]
+    fn remaining_template_product() {
+        let solved = Possibilities::try_from(EASY_SOLVED).unwrap();
+        assert_eq!(solved.remaining_template_product(), 1);
+
+        let mut contradiction = Possibilities::try_from(HARD).unwrap();
+        *contradiction.patterns_for_digit_mut(4) = Pattern::EMPTY;
+        assert_eq!(contradiction.remaining_template_product(), 0);
+
+        // never grows as clues are added, until solved
+        let mut puzzle = Possibilities::new();
+        let mut product = puzzle.remaining_template_product();
+        assert_eq!(product, u64::MAX);
+        let hard = Possibilities::try_from(HARD).unwrap();
+        let solution = <[[u8; 9]; 9]>::from(&SearchTree::new(&hard).run()[0]);
+        let cells = hard.clues().into_iter().map(|(row, col, _)| (row, col));
+        for (row, col) in cells.chain((0..81).map(|i| (i / 9, i % 9))) {
+            puzzle.set(row, col, solution[row][col]).unwrap();
+            let next = puzzle.remaining_template_product();
+            assert!(next <= product);
+            product = next;
+        }
+        assert_eq!(product, 1);
+    }
+
+    #[test]
+    fn digit_possible_somewhere() {
+        let puzzle = Possibilities::try_from(
+            ".8..46..9.1..794....9..3..2...7..95.....8...4.7....6.88..65.1.33.....84..964.8..7",
+        )
+        .unwrap();
+        let before = puzzle.clone();
+        let grids: Vec<[[u8; 9]; 9]> = SearchTree::new(&puzzle)
+            .run()
+            .iter()
+            .map(<[[u8; 9]; 9]>::from)
+            .collect();
+        assert_eq!(grids.len(), 6);
+
+        let mut searched = 0;
+        for (row, col) in (0..81).map(|i| (i / 9, i % 9)) {
+            for digit in 1..=9 {
+                let in_solution = grids.iter().any(|grid| grid[row][col] == digit);
+                let candidate = puzzle.patterns_for_digit(digit).has(row, col);
+                if candidate && !in_solution {
+                    searched += 1;
+                }
+                assert_eq!(
+                    puzzle.digit_possible_somewhere(row, col, digit),
+                    in_solution,
+                    "r{}c{} {}",
+                    row + 1,
+                    col + 1,
+                    digit
+                );
+            }
+        }
+        // some candidates are only ruled out by searching
+        assert!(searched > 0);
+        assert_eq!(puzzle, before);
+    }
+}
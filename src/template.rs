@@ -1,6 +1,10 @@
+use std::collections::HashMap;
 use std::sync::OnceLock;
 
-use crate::pattern::Pattern;
+use crate::{
+    format::exchange_format, parse::parse_exchange, pattern::Pattern, ImpossiblePuzzle, ParseError,
+    Possibilities,
+};
 
 /// A [`Pattern`] representing a legal layout for a single digit,
 /// but stored in only two bytes instead of 12.
@@ -15,6 +19,23 @@ pub struct Template(u16);
 #[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct Solution(pub [Template; 9]);
 
+/// [`Solution`] which remembers which cells were given as clues.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct AnnotatedSolution {
+    pub solution: Solution,
+    pub givens: Pattern,
+}
+
+/// One cell of a [`Solution`], with what a renderer needs to draw it.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct CellRender {
+    pub row: usize,
+    pub col: usize,
+    pub digit: u8,
+    /// Was the digit given as a clue?
+    pub is_given: bool,
+}
+
 fn leak_vec_as_array<T, const N: usize>(vec: Vec<T>) -> &'static [T; N] {
     // It's hard to allocate on the heap.
     // We'll use `std` to do it for us.
@@ -34,41 +55,116 @@ fn leak_vec_as_array<T, const N: usize>(vec: Vec<T>) -> &'static [T; N] {
 }
 
 impl Template {
+    /// Largest template index.
+    pub const MAX_INDEX: u16 = 46655;
+
+    /// Number of templates, without building the cache.
+    pub const fn valid_count() -> usize {
+        Template::MAX_INDEX as usize + 1
+    }
+
     /// Cached list of all patterns.
     pub fn all() -> &'static [Pattern; 46656] {
         static ALL: OnceLock<&'static [Pattern; 46656]> = OnceLock::new();
 
-        ALL.get_or_init(|| {
-            // Go row by row, choosing a free column in a free box.
-            fn fill(build: Pattern, cols: u16, boxes: u16, row: usize, into: &mut Vec<Pattern>) {
-                if row == 9 {
-                    into.push(build);
-                    return;
-                }
-                for col in 0..9 {
-                    let box_idx = row / 3 * 3 + col / 3;
-                    if (1 << col) & cols == 0 && (1 << box_idx) & boxes == 0 {
-                        fill(
-                            build.with(row, col),
-                            cols | (1 << col),
-                            boxes | (1 << box_idx),
-                            row + 1,
-                            into,
-                        );
-                    }
+        ALL.get_or_init(|| leak_vec_as_array(Template::compute_all()))
+    }
+
+    /// List of all patterns, computed from scratch.  [`Template::all`] caches
+    /// this; it's exposed to measure the cost of building the cache.
+    #[doc(hidden)]
+    pub fn compute_all() -> Vec<Pattern> {
+        // Go row by row, choosing a free column in a free box.
+        fn fill(build: Pattern, cols: u16, boxes: u16, row: usize, into: &mut Vec<Pattern>) {
+            if row == 9 {
+                into.push(build);
+                return;
+            }
+            for col in 0..9 {
+                let box_idx = row / 3 * 3 + col / 3;
+                if (1 << col) & cols == 0 && (1 << box_idx) & boxes == 0 {
+                    fill(
+                        build.with(row, col),
+                        cols | (1 << col),
+                        boxes | (1 << box_idx),
+                        row + 1,
+                        into,
+                    );
                 }
             }
+        }
+
+        let mut vec = Vec::new();
+        fill(Pattern::EMPTY, 0, 0, 0, &mut vec);
+        vec
+    }
+
+    /// Self-check the cached list: 46656 distinct patterns, each a legal
+    /// layout for a single digit.
+    pub fn verify() -> bool {
+        let all = Template::all();
+
+        let mut sorted = all.to_vec();
+        sorted.sort();
+        sorted.dedup();
+
+        // exactly one cell in every row, column, and box
+        let units: Vec<Pattern> = (0..9)
+            .flat_map(|i| {
+                [
+                    Pattern::row_mask(i),
+                    Pattern::col_mask(i),
+                    Pattern::box_mask(i),
+                ]
+            })
+            .collect();
+        let is_valid_layout = |p: Pattern| units.iter().all(|&unit| (p & unit).count() == 1);
+
+        sorted.len() == 46656 && all.iter().all(|&p| is_valid_layout(p))
+    }
 
-            let mut vec = Vec::new();
-            fill(Pattern::EMPTY, 0, 0, 0, &mut vec);
+    /// Cached list of all patterns as one column bit mask per row, in the
+    /// same order as [`Template::all`].
+    ///
+    /// For overlap checks, [`Pattern::intersects`] is still faster; the search
+    /// uses that.  See `benches/templates.rs`.
+    pub fn all_precomputed_row_masks() -> &'static [[u16; 9]; 46656] {
+        static MASKS: OnceLock<&'static [[u16; 9]; 46656]> = OnceLock::new();
+
+        MASKS.get_or_init(|| {
+            let vec = Template::all().iter().map(|p| p.to_row_bits()).collect();
             leak_vec_as_array(vec)
         })
     }
 
+    /// The template with this layout, if it's a legal layout for one digit.
+    pub fn from_pattern(pattern: Pattern) -> Option<Template> {
+        static INDEX: OnceLock<HashMap<Pattern, Template>> = OnceLock::new();
+
+        INDEX
+            .get_or_init(|| {
+                Template::all()
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &p)| (p, Template(i as u16)))
+                    .collect()
+            })
+            .get(&pattern)
+            .copied()
+    }
+
     pub fn as_pattern(self) -> Pattern {
         Template::all()[self.0 as usize]
     }
 
+    /// Can both templates be in one solution?  True if they don't overlap.
+    ///
+    /// Checks the cached [`Pattern`]s, which is faster than comparing
+    /// [row masks](Template::all_precomputed_row_masks).
+    pub fn compatible_with(self, other: Template) -> bool {
+        !self.as_pattern().intersects(other.as_pattern())
+    }
+
     /// Templates that are subsets of `possible`.
     pub fn within(possible: Pattern) -> impl Iterator<Item = Template> {
         Template::all()
@@ -78,6 +174,133 @@ impl Template {
             .filter(move |(_i, pattern)| pattern.is_subset(possible))
             .map(|(i, _pattern)| Template(i as u16))
     }
+
+    /// Templates that are subsets of `p`, like [`Template::within`].
+    ///
+    /// Skips checking each template when `p` is full or empty.
+    pub fn all_for_pattern(p: Pattern) -> impl Iterator<Item = Template> {
+        let (count, check) = match p {
+            Pattern::FULL => (Template::all().len(), false),
+            Pattern::EMPTY => (0, false),
+            _ => (Template::all().len(), true),
+        };
+        (0..count)
+            .map(|i| Template(i as u16))
+            .filter(move |t| !check || t.as_pattern().is_subset(p))
+    }
+
+    /// Templates that are subsets of `possible`, like [`Template::within`].
+    ///
+    /// Only templates whose first-row cell is possible are checked, which is
+    /// much faster when `possible` is sparse.  Yields the same templates in
+    /// the same order as `within`.
+    pub fn within_lazy(possible: Pattern) -> impl Iterator<Item = Template> {
+        (0..9)
+            .filter(move |&col| possible.has(0, col))
+            .flat_map(|col| Template::templates_starting_in_col(0, col))
+            .copied()
+            .filter(move |t| t.as_pattern().is_subset(possible))
+    }
+
+    /// Templates that are subsets of `possible`, like [`Template::within`].
+    ///
+    /// Like [`Template::within_lazy`], but uses whichever row narrows down the
+    /// templates the most, not just the first.  Yields the same templates in
+    /// the same order as `within`.
+    pub fn within_fast(possible: Pattern) -> impl Iterator<Item = Template> {
+        let candidates = |row| -> usize {
+            (0..9)
+                .filter(|&col| possible.has(row, col))
+                .map(|col| Template::templates_starting_in_col(row, col).len())
+                .sum()
+        };
+        let row = (0..9).min_by_key(|&row| candidates(row)).unwrap();
+
+        let mut templates: Vec<Template> = (0..9)
+            .filter(|&col| possible.has(row, col))
+            .flat_map(|col| Template::templates_starting_in_col(row, col))
+            .copied()
+            .filter(|t| t.as_pattern().is_subset(possible))
+            .collect();
+        templates.sort_unstable();
+        templates.into_iter()
+    }
+
+    /// Templates that are subsets of `possible`, like [`Template::within`].
+    ///
+    /// Instead of checking every template, this builds layouts directly from
+    /// the cells of `possible`, row by row.  This is much faster when
+    /// `possible` has few cells, and slower when it has many.
+    /// Patterns with fewer than 9 cells contain no templates at all.
+    pub fn within_sparse(possible: Pattern) -> Vec<Template> {
+        // Like `compute_all`, restricted to `possible`.
+        fn fill(
+            possible: Pattern,
+            build: Pattern,
+            cols: u16,
+            boxes: u16,
+            row: usize,
+            into: &mut Vec<Template>,
+        ) {
+            if row == 9 {
+                into.push(Template::from_pattern(build).unwrap());
+                return;
+            }
+            for col in 0..9 {
+                let box_idx = row / 3 * 3 + col / 3;
+                if possible.has(row, col) && (1 << col) & cols == 0 && (1 << box_idx) & boxes == 0 {
+                    fill(
+                        possible,
+                        build.with(row, col),
+                        cols | (1 << col),
+                        boxes | (1 << box_idx),
+                        row + 1,
+                        into,
+                    );
+                }
+            }
+        }
+
+        let mut vec = Vec::new();
+        if possible.count() >= 9 {
+            fill(possible, Pattern::EMPTY, 0, 0, 0, &mut vec);
+        }
+        vec
+    }
+
+    /// Templates within each digit's [`Pattern`], as `(digit, templates)` with
+    /// 0-indexed digits, sorted from fewest to most templates.
+    ///
+    /// This is the order the solver searches digits in.
+    pub fn sorted_by_restriction(possibles: &[Pattern; 9]) -> [(usize, Vec<Template>); 9] {
+        let mut templates: [(usize, Vec<Template>); 9] = Default::default();
+        for digit in 0..9 {
+            templates[digit] = (digit, Template::within(possibles[digit]).collect());
+        }
+
+        // Search digits from most- to least-restricted.
+        //   - If the puzzle has a unique solution then this order doesn't do much.
+        //   - If there are only a few clues, this makes it way faster.  :-)
+        //   - Downside: adding clues makes solution ordering unstable.  :-(
+        templates.sort_by_key(|(_digit, possible)| possible.len());
+        templates
+    }
+
+    /// Cached list of templates whose cell in `row` is in column `col`.
+    pub fn templates_starting_in_col(row: usize, col: usize) -> &'static [Template] {
+        static BY_ROW_COL: OnceLock<[[Vec<Template>; 9]; 9]> = OnceLock::new();
+
+        &BY_ROW_COL.get_or_init(|| {
+            let mut table: [[Vec<Template>; 9]; 9] = Default::default();
+            for (i, pattern) in Template::all().iter().enumerate() {
+                for (row, by_col) in table.iter_mut().enumerate() {
+                    let col = (0..9).find(|&col| pattern.has(row, col)).unwrap();
+                    by_col[col].push(Template(i as u16));
+                }
+            }
+            table
+        })[row][col]
+    }
 }
 
 impl Solution {
@@ -102,12 +325,184 @@ impl Solution {
         panic!("empty cell");
     }
 
-    pub fn to_grid(&self) -> Vec<u8> {
+    /// Template index for each digit.  Lossless, in 18 bytes.
+    pub fn pack(&self) -> [u16; 9] {
+        self.0.map(|t| t.0)
+    }
+
+    /// Read the form written by [`Solution::pack`].
+    ///
+    /// `None` if an index is out of range or the templates overlap.
+    pub fn unpack(packed: [u16; 9]) -> Option<Solution> {
+        if packed.iter().any(|&i| i as usize >= Template::all().len()) {
+            return None;
+        }
+        Some(Solution(packed.map(Template))).filter(|s| s.is_valid())
+    }
+
+    /// Number of cells already determined in `puzzle` which agree with this
+    /// solution.
+    ///
+    /// This counts digits found by logic as well as the original clues.
+    pub fn count_clues(&self, puzzle: &Possibilities) -> u32 {
         (0..81)
-            .into_iter()
-            .map(|i| self.cell(i / 9, i % 9))
+            .filter(|i| puzzle.get(i / 9, i % 9) == Some(self.cell(i / 9, i % 9)))
+            .count() as u32
+    }
+
+    /// Cells where the solutions disagree, as `(row, col, self_digit, other_digit)`.
+    pub fn diff(&self, other: &Solution) -> Vec<(usize, usize, u8, u8)> {
+        let mut out = Vec::new();
+        for row in 0..9 {
+            for col in 0..9 {
+                let (this, that) = (self.cell(row, col), other.cell(row, col));
+                if this != that {
+                    out.push((row, col, this, that));
+                }
+            }
+        }
+        out
+    }
+
+    /// JSON object with a 9×9 array of digits: `{"cells": [[1,2,...],...]}`.
+    pub fn fmt_json(&self) -> String {
+        let rows: Vec<String> = (0..9)
+            .map(|row| {
+                let cells: Vec<String> =
+                    (0..9).map(|col| self.cell(row, col).to_string()).collect();
+                format!("[{}]", cells.join(","))
+            })
+            .collect();
+        format!("{{\"cells\":[{}]}}", rows.join(","))
+    }
+
+    /// Sudoku Exchange form:  81 digits in row-major order.
+    pub fn to_sudoku_exchange_format(&self) -> String {
+        exchange_format(&self.to_grid())
+    }
+
+    /// Read the form written by [`Solution::to_sudoku_exchange_format`].
+    ///
+    /// [`ParseError::Impossible`] if some cell is empty or the grid breaks
+    /// the rules.
+    pub fn from_sudoku_exchange_format(s: &str) -> Result<Solution, ParseError> {
+        Solution::try_from(parse_exchange(s)?).map_err(|ImpossiblePuzzle| ParseError::Impossible)
+    }
+
+    /// Random solution grid, though not uniformly random.  `rng(n)` must
+    /// return a random index in `0..n`.
+    ///
+    /// Digits are placed one at a time, each choosing uniformly among the
+    /// templates which still fit, and backtracking on a dead end.  This is
+    /// closer to uniform over all grids than taking the first solution of a
+    /// shuffled search, but not exactly uniform:  a choice leading to fewer
+    /// completions is as likely as one leading to more.
+    pub fn sample_random(mut rng: impl FnMut(usize) -> usize) -> Solution {
+        fn fill(
+            digit: usize,
+            filled: Pattern,
+            solution: &mut Solution,
+            rng: &mut dyn FnMut(usize) -> usize,
+        ) -> bool {
+            if digit == 9 {
+                return true;
+            }
+
+            // Fisher-Yates, one pick at a time, so a success stops early.
+            let mut fits: Vec<Template> = Template::within(!filled).collect();
+            for i in 0..fits.len() {
+                let j = i + rng(fits.len() - i);
+                fits.swap(i, j);
+
+                solution.0[digit] = fits[i];
+                if fill(digit + 1, filled | fits[i].as_pattern(), solution, rng) {
+                    return true;
+                }
+            }
+            false
+        }
+
+        let mut solution = Solution::default();
+        let found = fill(0, Pattern::EMPTY, &mut solution, &mut rng);
+        assert!(found, "the empty grid has solutions");
+        solution
+    }
+
+    /// Solution with each digit (0-indexed) in the cells of its [`Pattern`].
+    ///
+    /// `None` if some pattern isn't a legal layout, or the layouts overlap.
+    pub fn from_patterns(patterns: [Pattern; 9]) -> Option<Solution> {
+        let mut solution = Solution::default();
+        for (template, pattern) in solution.0.iter_mut().zip(patterns) {
+            *template = Template::from_pattern(pattern)?;
+        }
+        Some(solution).filter(|s| s.is_valid())
+    }
+
+    /// Every cell in row-major order, marking those in `givens` as clues.
+    pub fn render_cells(&self, givens: Pattern) -> Vec<CellRender> {
+        (0..81)
+            .map(|i| CellRender {
+                row: i / 9,
+                col: i % 9,
+                digit: self.cell(i / 9, i % 9),
+                is_given: givens.has(i / 9, i % 9),
+            })
             .collect()
     }
+
+    pub fn to_grid(&self) -> Vec<u8> {
+        (0..81).map(|i| self.cell(i / 9, i % 9)).collect()
+    }
+}
+
+impl AnnotatedSolution {
+    /// Digit in the cell, and whether it was given as a clue.
+    pub fn cell(&self, row: usize, col: usize) -> (u8, bool) {
+        (self.solution.cell(row, col), self.givens.has(row, col))
+    }
+
+    /// Every cell in row-major order.  See [`Solution::render_cells`].
+    pub fn render_cells(&self) -> Vec<CellRender> {
+        self.solution.render_cells(self.givens)
+    }
+}
+
+impl From<&Solution> for [[u8; 9]; 9] {
+    /// Digit (1 through 9) in each cell.
+    fn from(solution: &Solution) -> [[u8; 9]; 9] {
+        std::array::from_fn(|row| std::array::from_fn(|col| solution.cell(row, col)))
+    }
+}
+
+impl TryFrom<[[u8; 9]; 9]> for Solution {
+    type Error = ImpossiblePuzzle;
+
+    /// Read a filled grid.  Fails if some cell is empty or the grid breaks the
+    /// rules.
+    fn try_from(grid: [[u8; 9]; 9]) -> Result<Solution, ImpossiblePuzzle> {
+        let mut patterns = [Pattern::EMPTY; 9];
+        for (row, digits) in grid.iter().enumerate() {
+            for (col, &digit) in digits.iter().enumerate() {
+                match digit {
+                    1..=9 => {
+                        patterns[digit as usize - 1] = patterns[digit as usize - 1].with(row, col)
+                    }
+                    _ => return Err(ImpossiblePuzzle),
+                }
+            }
+        }
+        Solution::from_patterns(patterns).ok_or(ImpossiblePuzzle)
+    }
+}
+
+impl TryFrom<&str> for Solution {
+    type Error = ParseError;
+
+    /// See [`Solution::from_sudoku_exchange_format`].
+    fn try_from(input: &str) -> Result<Solution, ParseError> {
+        Solution::from_sudoku_exchange_format(input)
+    }
 }
 
 impl std::fmt::Display for Solution {
@@ -122,3 +517,220 @@ impl std::fmt::Display for Solution {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_random_digits_are_evenly_spread() {
+        const SAMPLES: usize = 180;
+        let mut rng = crate::test_rng(7);
+
+        // [row][col][digit] -> times seen
+        let mut counts = [[[0usize; 9]; 9]; 9];
+        for _ in 0..SAMPLES {
+            let solution = Solution::sample_random(&mut rng);
+            assert!(solution.is_valid());
+            for i in 0..81 {
+                let digit = solution.cell(i / 9, i % 9) as usize;
+                counts[i / 9][i % 9][digit - 1] += 1;
+            }
+        }
+
+        // Pearson's statistic over every cell's digits:  648 degrees of
+        // freedom, so a mean of 648 and a deviation of 36.
+        let expected = SAMPLES as f64 / 9.;
+        let statistic: f64 = counts
+            .iter()
+            .flatten()
+            .flatten()
+            .map(|&seen| (seen as f64 - expected).powi(2) / expected)
+            .sum();
+        assert!(statistic < 648. + 6. * 36., "statistic {}", statistic);
+    }
+
+    #[test]
+    fn json_cells() {
+        use crate::json::{parse, Value};
+
+        let solution = Solution::sample_random(crate::test_rng(1));
+        let grid = <[[u8; 9]; 9]>::from(&solution);
+        let rows = grid.map(|row| Value::Array(row.map(|d| Value::Number(d as u64)).into()));
+        let expected = Value::Object(vec![("cells".to_string(), Value::Array(rows.into()))]);
+        assert_eq!(parse(&solution.fmt_json()), Some(expected));
+    }
+
+    #[test]
+    fn valid_count() {
+        assert_eq!(
+            Template::within(Pattern::FULL).count(),
+            Template::valid_count()
+        );
+        assert_eq!(Template::all().len(), Template::valid_count());
+        assert!(Template::from_pattern(Template::all()[Template::MAX_INDEX as usize]).is_some());
+    }
+
+    #[test]
+    fn sorted_by_restriction() {
+        let puzzle = Possibilities::try_from(
+            "4.....8.5.3..........7......2.....6.....8.4......1.......6.3.7.5..2.....1.4......",
+        )
+        .unwrap();
+        let sorted = Template::sorted_by_restriction(puzzle.patterns());
+
+        let counts = puzzle.remaining_template_counts();
+        let fewest = (0..9).min_by_key(|&digit| counts[digit]).unwrap();
+        assert_eq!(sorted[0].1.len(), counts[fewest]);
+        assert!(sorted.windows(2).all(|w| w[0].1.len() <= w[1].1.len()));
+
+        let mut digits = sorted.each_ref().map(|(digit, _)| *digit);
+        digits.sort();
+        assert_eq!(digits, [0, 1, 2, 3, 4, 5, 6, 7, 8]);
+        for (digit, templates) in &sorted {
+            assert_eq!(templates.len(), counts[*digit]);
+        }
+    }
+
+    #[test]
+    fn compatible_with() {
+        let solution = Possibilities::try_from(
+            "534678912672195348198342567859761423426853791713924856961537284287419635345286179",
+        )
+        .unwrap()
+        .unique()
+        .unwrap();
+        let [ones, twos, ..] = solution.0;
+        assert!(ones.compatible_with(twos));
+        assert!(twos.compatible_with(ones));
+        for (i, &a) in solution.0.iter().enumerate() {
+            assert!(!a.compatible_with(a));
+            for &b in &solution.0[i + 1..] {
+                assert!(a.compatible_with(b));
+            }
+        }
+    }
+
+    #[test]
+    fn diff() {
+        let solution = Solution::try_from(
+            "534678912672195348198342567859761423426853791713924856961537284287419635345286179",
+        )
+        .unwrap();
+        assert_eq!(solution.diff(&solution), []);
+
+        // swap 1s and 2s
+        let mut swapped = solution.clone();
+        swapped.0.swap(0, 1);
+        assert!(swapped.is_valid());
+        let diff = solution.diff(&swapped);
+        assert_eq!(diff.len(), 18);
+        assert_eq!(diff[..2], [(0, 7, 1, 2), (0, 8, 2, 1)]);
+        for &(row, col, this, that) in &diff {
+            assert_eq!(
+                (this, that),
+                (solution.cell(row, col), swapped.cell(row, col))
+            );
+            assert!(matches!((this, that), (1, 2) | (2, 1)));
+        }
+        assert_eq!(swapped.diff(&solution).len(), 18);
+    }
+
+    #[test]
+    fn exchange_format_round_trip() {
+        let text =
+            "534678912672195348198342567859761423426853791713924856961537284287419635345286179";
+        let solution = Solution::try_from(text).unwrap();
+        assert_eq!(solution.to_sudoku_exchange_format(), text);
+        assert_eq!(solution.to_string(), text);
+
+        let random = Solution::sample_random(crate::test_rng(3));
+        let read = Solution::from_sudoku_exchange_format(&random.to_sudoku_exchange_format());
+        assert_eq!(read, Ok(random));
+
+        // a puzzle isn't a solution
+        let mut puzzle = text.to_string();
+        puzzle.replace_range(0..1, "0");
+        assert_eq!(
+            Solution::try_from(puzzle.as_str()),
+            Err(ParseError::Impossible)
+        );
+    }
+
+    #[test]
+    fn all_for_pattern() {
+        assert_eq!(Template::all_for_pattern(Pattern::FULL).count(), 46656);
+        assert_eq!(
+            Template::all_for_pattern(Pattern::FULL).count(),
+            Template::MAX_INDEX as usize + 1
+        );
+        assert_eq!(Template::all_for_pattern(Pattern::EMPTY).count(), 0);
+
+        let possible = !Pattern::row_mask(0) | Pattern::bit(0, 4);
+        assert!(Template::all_for_pattern(possible).eq(Template::within(possible)));
+    }
+
+    #[test]
+    fn count_clues() {
+        let input =
+            ".8..46..9.1..794....9..3..2...7..95.....8...4.7....6.88..65.1.33.....84..964.8..7";
+        let puzzle = Possibilities::try_from(input).unwrap();
+        let clues = puzzle.clues().len() as u32;
+        assert!(clues > 17);
+        let determined = (0..81)
+            .filter(|i| puzzle.get(i / 9, i % 9).is_some())
+            .count() as u32;
+        assert!(determined >= clues);
+
+        let solutions = crate::SearchTree::new(&puzzle).run();
+        assert!(solutions.len() > 1);
+        for solution in &solutions {
+            assert_eq!(solution.count_clues(&puzzle), determined);
+        }
+        assert_eq!(solutions[0].count_clues(&Possibilities::new()), 0);
+
+        let other = Solution::try_from(
+            "534678912672195348198342567859761423426853791713924856961537284287419635345286179",
+        )
+        .unwrap();
+        assert!(other.count_clues(&puzzle) < clues);
+        let filled = Possibilities::try_from(other.to_string().as_str()).unwrap();
+        assert_eq!(other.count_clues(&filled), 81);
+    }
+
+    /// Random patterns, from sparse to dense, each holding a few templates.
+    fn random_possibles(seed: u64) -> Vec<Pattern> {
+        let mut rng = crate::test_rng(seed);
+        (0..24)
+            .map(|i| {
+                let noise = crate::test_pattern(&mut rng, [0, 10, 30, 60][i % 4]);
+                (0..1 + i % 3)
+                    .map(|_| Template::all()[rng(Template::all().len())])
+                    .fold(noise, |acc, t| acc | t)
+            })
+            .chain([Pattern::EMPTY, Pattern::FULL])
+            .collect()
+    }
+
+    #[test]
+    fn within_fast_matches_within() {
+        for possible in random_possibles(11) {
+            let fast: Vec<_> = Template::within_fast(possible).collect();
+            let slow: Vec<_> = Template::within(possible).collect();
+            assert!(!slow.is_empty() || possible == Pattern::EMPTY);
+            assert_eq!(fast, slow, "{}", possible);
+        }
+    }
+
+    #[test]
+    fn within_sparse_matches_within() {
+        for possible in random_possibles(13) {
+            let mut sparse = Template::within_sparse(possible);
+            sparse.sort_unstable();
+            let slow: Vec<_> = Template::within(possible).collect();
+            assert_eq!(sparse, slow, "{}", possible);
+        }
+        let eight = Template::all()[0] & !Pattern::bit(0, 0);
+        assert_eq!(Template::within_sparse(eight), []);
+    }
+}